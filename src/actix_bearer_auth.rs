@@ -0,0 +1,90 @@
+use crate::error::{ApiError, AppError};
+use actix_web::{
+    body::EitherBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use std::sync::Arc;
+
+/// Rejects requests that do not carry `Authorization: Bearer <token>` with `401 Unauthorized`.
+pub struct BearerAuth {
+    token: Arc<str>,
+}
+
+impl BearerAuth {
+    pub fn new(token: impl Into<Arc<str>>) -> Self {
+        BearerAuth {
+            token: token.into(),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for BearerAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = BearerAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(BearerAuthMiddleware {
+            service,
+            token: self.token.clone(),
+        })
+    }
+}
+
+pub struct BearerAuthMiddleware<S> {
+    service: S,
+    token: Arc<str>,
+}
+
+impl<S, B> Service<ServiceRequest> for BearerAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let authorized = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|presented| constant_time_eq(presented.trim(), &self.token));
+
+        if authorized {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        }
+
+        let mut res = req.error_response(AppError::from(ApiError::Unauthorized));
+        res.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+        Box::pin(async move { Ok(res.map_into_right_body()) })
+    }
+}
+
+// Compare without short-circuiting so response timing does not reveal a matching prefix.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
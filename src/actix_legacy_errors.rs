@@ -0,0 +1,76 @@
+use crate::error::{AppError, LEGACY_ERRORS_HEADER};
+use actix_web::{
+    body::EitherBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpResponse,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+
+/// Rewrites `AppError` bodies to the legacy `"Something went wrong: ..."` JSON string for
+/// clients that send `LEGACY_ERRORS_HEADER`. The status code is left as is.
+pub struct LegacyErrors;
+
+impl<S, B> Transform<S, ServiceRequest> for LegacyErrors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = LegacyErrorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(LegacyErrorsMiddleware { service })
+    }
+}
+
+pub struct LegacyErrorsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for LegacyErrorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let wants_legacy = req
+            .headers()
+            .get(LEGACY_ERRORS_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| matches!(value.trim(), "1" | "true"));
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            if !wants_legacy {
+                return Ok(res.map_into_left_body());
+            }
+
+            let legacy_body = res
+                .response()
+                .error()
+                .and_then(|error| error.as_error::<AppError>())
+                .map(AppError::legacy_body);
+
+            Ok(match legacy_body {
+                Some(body) => {
+                    let status = res.status();
+                    res.into_response(HttpResponse::build(status).json(body))
+                        .map_into_right_body()
+                }
+                None => res.map_into_left_body(),
+            })
+        })
+    }
+}
@@ -1,10 +1,19 @@
+use crate::redact;
 use actix_web::{
-    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web::BytesMut,
     Error,
 };
-use futures::future::{ok, Ready};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use futures::StreamExt;
+use std::rc::Rc;
 use std::sync::Arc;
 
+// JSON bodies up to this size are logged along with the route; larger ones are skipped.
+const MAX_LOGGED_BODY_BYTES: usize = 16 * 1024;
+
+/// Logs every request line, and small JSON bodies, with group key material redacted.
 pub struct RouteDumper {
     logger: Arc<dyn Fn(&str) + Send + Sync>,
 }
@@ -22,7 +31,7 @@ impl RouteDumper {
 
 impl<S, B> Transform<S, ServiceRequest> for RouteDumper
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -34,32 +43,78 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ok(RouteDumperMiddleware {
-            service,
+            service: Rc::new(service),
             logger: self.logger.clone(),
         })
     }
 }
 
 pub struct RouteDumperMiddleware<S> {
-    service: S,
+    service: Rc<S>,
     logger: Arc<dyn Fn(&str) + Send + Sync>,
 }
 
 impl<S, B> Service<ServiceRequest> for RouteDumperMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
     type Response = ServiceResponse<B>;
     type Error = Error;
-    type Future = S::Future;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     actix_web::dev::forward_ready!(service);
 
-    fn call(&self, req: ServiceRequest) -> Self::Future {
-        let route_info = format!("{} {}", req.method(), req.path());
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let route_info = match req.query_string() {
+            "" => format!("{} {}", req.method(), redact::redact(req.path())),
+            query => format!(
+                "{} {}?{}",
+                req.method(),
+                redact::redact(req.path()),
+                redact::redact(query)
+            ),
+        };
         (self.logger)(&route_info);
-        self.service.call(req)
+
+        if !has_loggable_body(&req) {
+            return Box::pin(self.service.call(req));
+        }
+
+        let service = self.service.clone();
+        let logger = self.logger.clone();
+        Box::pin(async move {
+            let mut payload = req.take_payload();
+            let mut body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                body.extend_from_slice(&chunk?);
+            }
+            let body = body.freeze();
+
+            match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(json) => logger(&format!("body {}", redact::redact_json(&json))),
+                Err(_) => logger("body <unparseable JSON>"),
+            }
+
+            req.set_payload(Payload::from(body));
+            service.call(req).await
+        })
     }
 }
+
+/// Whether the request declares a JSON body small enough to buffer for logging.
+fn has_loggable_body(req: &ServiceRequest) -> bool {
+    let is_json = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+
+    is_json && length.is_some_and(|length| length > 0 && length <= MAX_LOGGED_BODY_BYTES)
+}
@@ -1,16 +1,17 @@
 #![allow(unused)]
+use crate::config::ServerConfig;
 use crate::constants::TAG;
 use crate::jni_globals;
 use crate::logging::android_log;
 use crate::server;
-use crate::server::start;
+use crate::server::start_with_config;
 use crate::{log_debug, log_error, log_info};
 use jni::errors::Result as JniResult;
 use jni::errors::ThrowRuntimeExAndDefault;
 use jni::jni_sig;
 use jni::jni_str;
 use jni::objects::{JClass, JObject, JString, JValue};
-use jni::sys::{jint, jstring};
+use jni::sys::{jboolean, jint, jlong, jstring, JNI_TRUE};
 use jni::{Env, EnvUnowned};
 use std::time::Duration;
 use veilid_core::veilid_core_setup_android;
@@ -36,35 +37,134 @@ pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_Sn
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_SnowbirdBridge_startServer(
-    mut env: EnvUnowned,
+    env: EnvUnowned,
+    clazz: JClass,
+    context: JObject,
+    backend_base_directory: JString,
+    server_socket_path: JString,
+) -> jstring {
+    start_server(
+        env,
+        clazz,
+        context,
+        backend_base_directory,
+        server_socket_path,
+        |_env| Ok(ServerConfig::from_env()),
+    )
+}
+
+/// Like `startServer`, but with an explicit config. A null `bindAddress`, or 0 for `port` or
+/// any of the limits, keeps the default; apps that only talk over the socket pass
+/// `enableTcp = false`. A null or blank `apiToken` makes the server generate one; read it
+/// back with `getApiToken`.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_SnowbirdBridge_startServerWithConfig(
+    env: EnvUnowned,
     clazz: JClass,
     context: JObject,
     backend_base_directory: JString,
     server_socket_path: JString,
+    bind_address: JString,
+    port: jint,
+    enable_tcp: jboolean,
+    enable_socket: jboolean,
+    api_token: JString,
+    max_upload_bytes: jlong,
+    refresh_concurrency: jint,
+    refresh_deadline_secs: jlong,
 ) -> jstring {
+    start_server(
+        env,
+        clazz,
+        context,
+        backend_base_directory,
+        server_socket_path,
+        move |env| {
+            let mut config = ServerConfig::from_env();
+            if !bind_address.is_null() {
+                let bind_address = bind_address.try_to_string(env)?;
+                config.bind_address = bind_address.parse().map_err(|_| {
+                    jni::errors::Error::ParseFailed(format!("Invalid bind address: {bind_address}"))
+                })?;
+            }
+            if port != 0 {
+                config.port = u16::try_from(port).map_err(|_| {
+                    jni::errors::Error::ParseFailed(format!("Invalid port: {port}"))
+                })?;
+            }
+            config.enable_tcp = enable_tcp == JNI_TRUE;
+            config.enable_socket = enable_socket == JNI_TRUE;
+            if !api_token.is_null() {
+                config.api_token = Some(api_token.try_to_string(env)?);
+            }
+            if max_upload_bytes != 0 {
+                config.max_upload_bytes = u64::try_from(max_upload_bytes).map_err(|_| {
+                    jni::errors::Error::ParseFailed(format!(
+                        "Invalid max upload bytes: {max_upload_bytes}"
+                    ))
+                })?;
+            }
+            if refresh_concurrency != 0 {
+                config.refresh_concurrency =
+                    usize::try_from(refresh_concurrency).map_err(|_| {
+                        jni::errors::Error::ParseFailed(format!(
+                            "Invalid refresh concurrency: {refresh_concurrency}"
+                        ))
+                    })?;
+            }
+            if refresh_deadline_secs != 0 {
+                let secs = u64::try_from(refresh_deadline_secs).map_err(|_| {
+                    jni::errors::Error::ParseFailed(format!(
+                        "Invalid refresh deadline: {refresh_deadline_secs}"
+                    ))
+                })?;
+                config.refresh_deadline = Duration::from_secs(secs);
+            }
+            config
+                .validate()
+                .map_err(|e| jni::errors::Error::ParseFailed(e.to_string()))?;
+            Ok(config)
+        },
+    )
+}
+
+fn start_server<F>(
+    mut env: EnvUnowned,
+    clazz: JClass,
+    context: JObject,
+    backend_base_directory: JString,
+    server_socket_path: JString,
+    read_config: F,
+) -> jstring
+where
+    F: FnOnce(&mut Env) -> JniResult<ServerConfig>,
+{
     log_debug!(TAG, "Bridge: starting");
 
     // Initialize JNI globals, smoke-test the Java callback, and read Java args while
     // EnvUnowned is still available. veilid_core_setup_android consumes env/context.
-    let (backend_base_directory, server_socket_path, output) = env
-        .with_env(|env| -> JniResult<(String, String, jstring)> {
-            jni_globals::init_jni(env, clazz).map_err(|e| {
-                jni::errors::Error::ParseFailed(format!("Failed to initialize JNI globals: {e}"))
-            })?;
-            jni_smoke_test(env)?;
-
-            let backend_base_directory = backend_base_directory.try_to_string(env)?;
-            let server_socket_path = server_socket_path.try_to_string(env)?;
-            let output = JString::from_str(
-                env,
-                format!("Server started on Unix socket: {server_socket_path}"),
-            )?
-            .into_raw();
-
-            log_debug!(TAG, "JNI stuff successful");
-
-            Ok((backend_base_directory, server_socket_path, output))
-        })
+    let (backend_base_directory, server_socket_path, mut config, output) = env
+        .with_env(
+            |env| -> JniResult<(String, String, ServerConfig, jstring)> {
+                jni_globals::init_jni(env, clazz).map_err(|e| {
+                    jni::errors::Error::ParseFailed(format!(
+                        "Failed to initialize JNI globals: {e}"
+                    ))
+                })?;
+                jni_smoke_test(env)?;
+
+                let backend_base_directory = backend_base_directory.try_to_string(env)?;
+                let server_socket_path = server_socket_path.try_to_string(env)?;
+                let config = read_config(env)?;
+                let output = JString::from_str(env, startup_message(&config, &server_socket_path))?
+                    .into_raw();
+
+                log_debug!(TAG, "JNI stuff successful");
+
+                Ok((backend_base_directory, server_socket_path, config, output))
+            },
+        )
         .resolve::<ThrowRuntimeExAndDefault>();
 
     // resolve() throws to Java and returns null on failure; do not start Veilid or the server.
@@ -74,10 +174,13 @@ pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_Sn
 
     veilid_core_setup_android(env, context);
 
+    // Settle the token before the server thread starts so `getApiToken` never races it.
+    server::ensure_api_token(&mut config);
+
     std::thread::spawn(move || {
         let runtime = tokio::runtime::Runtime::new().unwrap();
         runtime.block_on(async {
-            start(&backend_base_directory, &server_socket_path)
+            start_with_config(&backend_base_directory, &server_socket_path, config)
                 .await
                 .unwrap();
         });
@@ -88,6 +191,33 @@ pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_Sn
     output
 }
 
+/// Bearer token the host app must send on `/api` requests, or null before `startServer`.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_SnowbirdBridge_getApiToken(
+    mut env: EnvUnowned,
+    _clazz: JClass,
+) -> jstring {
+    env.with_env(|env| -> JniResult<jstring> {
+        match server::api_token() {
+            Some(token) => Ok(JString::from_str(env, token)?.into_raw()),
+            None => Ok(std::ptr::null_mut()),
+        }
+    })
+    .resolve::<ThrowRuntimeExAndDefault>()
+}
+
+fn startup_message(config: &ServerConfig, server_socket_path: &str) -> String {
+    if config.enable_socket {
+        format!("Server started on Unix socket: {server_socket_path}")
+    } else {
+        format!(
+            "Server started on http://{}:{}",
+            config.bind_address, config.port
+        )
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_SnowbirdBridge_stopServer(
@@ -102,24 +232,11 @@ pub extern "system" fn Java_net_opendasharchive_openarchive_services_snowbird_Sn
             // Create a runtime to handle async operations
             let runtime = tokio::runtime::Runtime::new().unwrap();
 
-            // Stop the backend server and clean up Veilid API
+            // Drain the web server, stop the backend, shut down Veilid and remove the socket.
             runtime.block_on(async {
-                // First stop the backend
-                match server::stop().await {
+                match server::shutdown().await {
                     Ok(_) => {
-                        log_info!(TAG, "Backend stopped successfully");
-
-                        // Get the backend to access Veilid API
-                        if let Ok(backend) = server::get_backend().await {
-                            // Shutdown Veilid API
-                            if let Some(veilid_api) = backend.get_veilid_api().await {
-                                veilid_api.shutdown().await;
-                                log_info!(TAG, "Veilid API shut down successfully");
-                            }
-                        }
-
-                        // Add a small delay to ensure tasks complete
-                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        log_info!(TAG, "Server stopped successfully");
                         Ok(true)
                     }
                     Err(e) => {
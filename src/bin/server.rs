@@ -1,15 +1,30 @@
 //! Desktop server binary for testing the save-dweb backend.
 //!
 //! Usage:
-//!   cargo run --bin save-server [-- <base_dir>]
+//!   cargo run --bin save-server [-- [--bind <addr>] [--port <port>] [--no-tcp] [--no-socket]
+//!                                   [--token <token>] [--max-upload-bytes <n>]
+//!                                   [--refresh-concurrency <n>] [--refresh-deadline <secs>]
+//!                                   [<base_dir>]]
 //!
-//! The server listens on:
-//!   - HTTP: http://0.0.0.0:8080
+//! By default the server listens on:
+//!   - HTTP: http://127.0.0.1:8080
 //!   - Unix socket: <base_dir>/save-server.sock
 //!
+//! Flags override the SAVE_BIND_ADDRESS, SAVE_PORT, SAVE_ENABLE_TCP, SAVE_ENABLE_SOCKET,
+//! SAVE_API_TOKEN, SAVE_MAX_UPLOAD_BYTES, SAVE_REFRESH_CONCURRENCY and
+//! SAVE_REFRESH_DEADLINE_SECS environment variables.
+//!
+//! `/api` routes require `Authorization: Bearer <token>`. Without --token or SAVE_API_TOKEN a
+//! random token is generated and printed at startup.
+//!
 //! Set RUST_LOG to control log verbosity, e.g.:
 //!   RUST_LOG=debug cargo run --bin save-server
+//!
+//! SIGINT (Ctrl+C) and SIGTERM drain in-flight requests, stop the backend and remove the
+//! socket file before exiting. `POST /api/shutdown` from the local machine does the same.
 
+use anyhow::{anyhow, Context};
+use save::config::ServerConfig;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -18,7 +33,11 @@ use std::path::PathBuf;
 async fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let base_dir = env::args().nth(1).unwrap_or_else(|| {
+    let (base_dir, mut config) = parse_args(env::args().skip(1))?;
+    config.validate()?;
+    let api_token = save::server::ensure_api_token(&mut config);
+
+    let base_dir = base_dir.unwrap_or_else(|| {
         let mut p = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         p.push("save-data");
         p.to_string_lossy().into_owned()
@@ -34,8 +53,104 @@ async fn main() -> anyhow::Result<()> {
 
     println!("save-server v{}", env!("CARGO_PKG_VERSION"));
     println!("  Data directory: {base_dir}");
-    println!("  Unix socket:    {socket_path}");
-    println!("  HTTP:           http://127.0.0.1:8080");
+    if config.enable_socket {
+        println!("  Unix socket:    {socket_path}");
+    }
+    if config.enable_tcp {
+        println!(
+            "  HTTP:           http://{}",
+            std::net::SocketAddr::new(config.bind_address, config.port)
+        );
+    }
+    println!("  API token:      {api_token}");
+
+    tokio::spawn(async {
+        wait_for_shutdown_signal().await;
+        println!("Shutting down...");
+        save::server::request_shutdown();
+    });
+
+    save::server::start_with_config(&base_dir, &socket_path, config).await
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> anyhow::Result<(Option<String>, ServerConfig)> {
+    let mut config = ServerConfig::from_env();
+    let mut base_dir = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bind" => {
+                let value = args.next().context("--bind requires an address")?;
+                config.bind_address = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid --bind address: {value}"))?;
+            }
+            "--port" => {
+                let value = args.next().context("--port requires a number")?;
+                config.port = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid --port value: {value}"))?;
+            }
+            "--token" => {
+                config.api_token = Some(args.next().context("--token requires a value")?);
+            }
+            "--max-upload-bytes" => {
+                let value = args
+                    .next()
+                    .context("--max-upload-bytes requires a number")?;
+                config.max_upload_bytes = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid --max-upload-bytes value: {value}"))?;
+            }
+            "--refresh-concurrency" => {
+                let value = args
+                    .next()
+                    .context("--refresh-concurrency requires a number")?;
+                config.refresh_concurrency = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid --refresh-concurrency value: {value}"))?;
+            }
+            "--refresh-deadline" => {
+                let value = args.next().context("--refresh-deadline requires seconds")?;
+                config.refresh_deadline = value
+                    .parse()
+                    .map(std::time::Duration::from_secs)
+                    .map_err(|_| anyhow!("Invalid --refresh-deadline value: {value}"))?;
+            }
+            "--no-tcp" => config.enable_tcp = false,
+            "--no-socket" => config.enable_socket = false,
+            flag if flag.starts_with("--") => return Err(anyhow!("Unknown flag: {flag}")),
+            _ if base_dir.is_none() => base_dir = Some(arg),
+            _ => return Err(anyhow!("Unexpected argument: {arg}")),
+        }
+    }
+
+    Ok((base_dir, config))
+}
+
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to install SIGTERM handler: {e}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
 
-    save::server::start(&base_dir, &socket_path).await
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
 }
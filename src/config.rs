@@ -0,0 +1,141 @@
+use crate::constants::TAG;
+use crate::log_error;
+use std::env;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+// Per-request upload cap.
+pub const DEFAULT_MAX_UPLOAD_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+// Repos refreshed at once by `refresh_group`.
+pub const DEFAULT_REFRESH_CONCURRENCY: usize = 4;
+
+// Overall time budget for `refresh_group`. Short enough that clients get partial results
+// before their own request timeouts.
+pub const DEFAULT_REFRESH_DEADLINE: Duration = Duration::from_secs(45);
+
+/// Where the HTTP API listens, and the limits it applies to requests.
+///
+/// Defaults match the historical behaviour: TCP on `127.0.0.1:8080` plus the Unix socket.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub enable_tcp: bool,
+    pub enable_socket: bool,
+    /// Bearer token required on `/api` routes. A random one is generated at startup when unset or blank.
+    pub api_token: Option<String>,
+    /// Largest upload accepted by a single request, in bytes.
+    pub max_upload_bytes: u64,
+    /// Repos refreshed at once by `POST /api/groups/{group_id}/refresh`.
+    pub refresh_concurrency: usize,
+    /// Time after which a group refresh returns with the repos that finished.
+    pub refresh_deadline: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: DEFAULT_PORT,
+            enable_tcp: true,
+            enable_socket: true,
+            api_token: None,
+            max_upload_bytes: DEFAULT_MAX_UPLOAD_BYTES,
+            refresh_concurrency: DEFAULT_REFRESH_CONCURRENCY,
+            refresh_deadline: DEFAULT_REFRESH_DEADLINE,
+        }
+    }
+}
+
+// Hand-written so the token never ends up in logs.
+impl fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("bind_address", &self.bind_address)
+            .field("port", &self.port)
+            .field("enable_tcp", &self.enable_tcp)
+            .field("enable_socket", &self.enable_socket)
+            .field("api_token", &self.api_token.as_ref().map(|_| "<redacted>"))
+            .field("max_upload_bytes", &self.max_upload_bytes)
+            .field("refresh_concurrency", &self.refresh_concurrency)
+            .field("refresh_deadline", &self.refresh_deadline)
+            .finish()
+    }
+}
+
+impl ServerConfig {
+    /// Defaults overridden by `SAVE_BIND_ADDRESS`, `SAVE_PORT`, `SAVE_ENABLE_TCP`,
+    /// `SAVE_ENABLE_SOCKET`, `SAVE_API_TOKEN`, `SAVE_MAX_UPLOAD_BYTES`,
+    /// `SAVE_REFRESH_CONCURRENCY` and `SAVE_REFRESH_DEADLINE_SECS`. Invalid values, including
+    /// a zero limit, are logged and ignored.
+    pub fn from_env() -> Self {
+        let defaults = ServerConfig::default();
+        ServerConfig {
+            bind_address: env_value("SAVE_BIND_ADDRESS").unwrap_or(defaults.bind_address),
+            port: env_value("SAVE_PORT").unwrap_or(defaults.port),
+            enable_tcp: env_flag("SAVE_ENABLE_TCP").unwrap_or(defaults.enable_tcp),
+            enable_socket: env_flag("SAVE_ENABLE_SOCKET").unwrap_or(defaults.enable_socket),
+            api_token: env::var("SAVE_API_TOKEN").ok(),
+            max_upload_bytes: env_value::<NonZeroU64>("SAVE_MAX_UPLOAD_BYTES")
+                .map_or(defaults.max_upload_bytes, NonZeroU64::get),
+            refresh_concurrency: env_value::<NonZeroUsize>("SAVE_REFRESH_CONCURRENCY")
+                .map_or(defaults.refresh_concurrency, NonZeroUsize::get),
+            refresh_deadline: env_value::<NonZeroU64>("SAVE_REFRESH_DEADLINE_SECS")
+                .map_or(defaults.refresh_deadline, |secs| {
+                    Duration::from_secs(secs.get())
+                }),
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.enable_tcp && !self.enable_socket {
+            return Err(anyhow::anyhow!(
+                "Server config disables both TCP and the Unix socket; nothing to listen on"
+            ));
+        }
+        if self.max_upload_bytes == 0
+            || self.refresh_concurrency == 0
+            || self.refresh_deadline.is_zero()
+        {
+            return Err(anyhow::anyhow!(
+                "Server config limits must be greater than zero: {self:?}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn env_value<T: FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse::<T>() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            log_error!(TAG, "Invalid {} value: {}, using default", name, value);
+            None
+        }
+    }
+}
+
+/// Parse a boolean flag, accepting `1/0`, `true/false`, `yes/no` and `on/off`.
+pub fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn env_flag(name: &str) -> Option<bool> {
+    let value = env::var(name).ok()?;
+    let flag = parse_flag(&value);
+    if flag.is_none() {
+        log_error!(TAG, "Invalid {} value: {}, using default", name, value);
+    }
+    flag
+}
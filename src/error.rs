@@ -1,13 +1,137 @@
 use crate::constants::TAG;
 use crate::log_error;
-use actix_web::{error::BlockingError, HttpResponse, ResponseError};
+use actix_web::{error::BlockingError, http::StatusCode, HttpResponse, ResponseError};
 use anyhow::anyhow;
 use anyhow::Error as AnyhowError;
 use base64_url::base64;
 use eyre::ErrReport;
+use serde_json::json;
+use thiserror::Error;
+
+/// Request header that switches error bodies back to the pre-`code` string format.
+pub const LEGACY_ERRORS_HEADER: &str = "X-Save-Legacy-Errors";
+
+/// Failures with a dedicated HTTP status and a stable `code` clients can branch on.
+///
+/// Anything else that reaches `AppError` is reported as `500 internal_error`.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Group not found: {0}")]
+    GroupNotFound(String),
+
+    #[error("Repo not found: {0}")]
+    RepoNotFound(String),
+
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+
+    #[error("File already exists: {0}")]
+    FileExists(String),
+
+    #[error("Job not found: {0}")]
+    JobNotFound(u64),
+
+    #[error("Not pinned: {0}")]
+    PinNotFound(String),
+
+    #[error("Version not found: {0}")]
+    VersionNotFound(String),
+
+    #[error("Repo is not writable: {0}")]
+    NotWritable(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+
+    #[error("Backend not ready: {0}")]
+    BackendNotReady(String),
+
+    #[error("Timed out waiting for peers: {0}")]
+    PeerTimeout(String),
+
+    #[error("Failed to decrypt file: {0}")]
+    DecryptFailure(String),
+
+    #[error("File too large: upload exceeds the {0} byte limit")]
+    PayloadTooLarge(u64),
+
+    #[error("Garbage collection unavailable: {0}")]
+    GcUnavailable(String),
+
+    #[error("Eviction unavailable: {0}")]
+    EvictionUnavailable(String),
+}
+
+impl ApiError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidKey(_) | ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::GroupNotFound(_)
+            | ApiError::RepoNotFound(_)
+            | ApiError::FileNotFound(_)
+            | ApiError::JobNotFound(_)
+            | ApiError::PinNotFound(_)
+            | ApiError::VersionNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::NotWritable(_) | ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::BackendNotReady(_)
+            | ApiError::GcUnavailable(_)
+            | ApiError::EvictionUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::PeerTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::DecryptFailure(_) | ApiError::FileExists(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidKey(_) => "invalid_key",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::GroupNotFound(_) => "group_not_found",
+            ApiError::RepoNotFound(_) => "repo_not_found",
+            ApiError::FileNotFound(_) => "file_not_found",
+            ApiError::FileExists(_) => "file_exists",
+            ApiError::JobNotFound(_) => "job_not_found",
+            ApiError::PinNotFound(_) => "pin_not_found",
+            ApiError::VersionNotFound(_) => "version_not_found",
+            ApiError::NotWritable(_) => "not_writable",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::BackendNotReady(_) => "backend_not_ready",
+            ApiError::PeerTimeout(_) => "peer_timeout",
+            ApiError::DecryptFailure(_) => "decrypt_failure",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::GcUnavailable(_) => "gc_unavailable",
+            ApiError::EvictionUnavailable(_) => "eviction_unavailable",
+        }
+    }
+}
 
 pub struct AppError(pub AnyhowError);
 
+impl AppError {
+    /// The typed error anywhere in the chain, so added context does not hide it.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        self.0
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<ApiError>())
+    }
+
+    /// Body used before errors carried a `code`, served when `LEGACY_ERRORS_HEADER` is set.
+    pub fn legacy_body(&self) -> String {
+        format!("Something went wrong: {}", self.0)
+    }
+}
+
 impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -27,21 +151,26 @@ impl std::fmt::Debug for AppError {
 // }
 
 impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        self.api_error()
+            .map_or(StatusCode::INTERNAL_SERVER_ERROR, ApiError::status_code)
+    }
+
     fn error_response(&self) -> HttpResponse {
         log_error!(TAG, "AppError occurred: {:?}", self);
 
-        // Keep legacy error body shape for bindings clients that parse a plain string.
-        // We still use 503 for readiness-related failures so callers can retry.
-        let error_msg = self.0.to_string();
-        let legacy_body = format!("Something went wrong: {error_msg}");
-        if error_msg.contains("Backend not ready")
-            || error_msg.contains("not initialized")
-            || error_msg.contains("Initialization")
-        {
-            return HttpResponse::ServiceUnavailable().json(legacy_body);
-        }
+        let code = self.api_error().map_or("internal_error", ApiError::code);
+        HttpResponse::build(self.status_code()).json(json!({
+            "status": "error",
+            "code": code,
+            "error": self.0.to_string()
+        }))
+    }
+}
 
-        HttpResponse::InternalServerError().json(legacy_body)
+impl From<ApiError> for AppError {
+    fn from(err: ApiError) -> Self {
+        AppError(AnyhowError::new(err))
     }
 }
 
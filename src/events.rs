@@ -0,0 +1,190 @@
+use crate::constants::TAG;
+use crate::jobs::DownloadJob;
+use crate::server::get_backend;
+use crate::{log_debug, log_info};
+use actix_web::{get, http::header, HttpResponse, Responder};
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use once_cell::sync::Lazy;
+use save_dweb_backend::backend::Backend;
+use save_dweb_backend::common::DHTEntity;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+use veilid_core::VeilidUpdate;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+// Comment lines keep idle SSE connections from being closed by proxies and clients.
+const SSE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Updates pushed to clients over `GET /api/events`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Attachment {
+        state: String,
+        public_internet_ready: bool,
+    },
+    DhtValueChange {
+        group_id: String,
+        repo_id: String,
+    },
+    BackendReady {
+        ready: bool,
+    },
+    UploadProgress {
+        repo_id: String,
+        file_name: String,
+        bytes_received: u64,
+        done: bool,
+    },
+    DownloadProgress(DownloadJob),
+    /// A file fetched from peers outside a job, by a download request or a pin sync.
+    FetchProgress {
+        group_id: String,
+        repo_id: String,
+        file_name: String,
+        bytes_received: u64,
+        done: bool,
+    },
+}
+
+impl ServerEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Attachment { .. } => "attachment",
+            ServerEvent::DhtValueChange { .. } => "dht_value_change",
+            ServerEvent::BackendReady { .. } => "backend_ready",
+            ServerEvent::UploadProgress { .. } => "upload_progress",
+            ServerEvent::DownloadProgress(_) => "download_progress",
+            ServerEvent::FetchProgress { .. } => "fetch_progress",
+        }
+    }
+
+    fn to_sse_frame(&self) -> Option<Bytes> {
+        let data = serde_json::to_string(self).ok()?;
+        Some(Bytes::from(format!(
+            "event: {}\ndata: {data}\n\n",
+            self.name()
+        )))
+    }
+}
+
+static EVENTS: Lazy<broadcast::Sender<ServerEvent>> =
+    Lazy::new(|| broadcast::channel(EVENT_CHANNEL_CAPACITY).0);
+
+// True while the server shuts down. Event streams never finish on their own, so they end
+// here instead of holding up the drain of in-flight responses.
+static CLOSING: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+
+/// End every open event stream. Streams opened later end at once until `reopen_streams`.
+pub fn close_streams() {
+    CLOSING.send_replace(true);
+}
+
+pub fn reopen_streams() {
+    CLOSING.send_replace(false);
+}
+
+async fn closing() {
+    let mut closing = CLOSING.subscribe();
+    let _ = closing.wait_for(|closing| *closing).await;
+}
+
+/// Broadcast an event to every connected client. Events are dropped when nobody listens.
+pub fn publish(event: ServerEvent) {
+    let _ = EVENTS.send(event);
+}
+
+pub fn subscribe() -> broadcast::Receiver<ServerEvent> {
+    EVENTS.subscribe()
+}
+
+/// Relay Veilid attachment changes and DHT value changes for joined repos until the
+/// backend's update channel closes.
+pub async fn forward_backend_updates(backend: &Backend) {
+    let Some(mut updates) = backend.subscribe_updates().await else {
+        log_info!(
+            TAG,
+            "No Veilid update receiver; backend events will not be relayed"
+        );
+        return;
+    };
+
+    loop {
+        match updates.recv().await {
+            Ok(VeilidUpdate::Attachment(attachment)) => publish(ServerEvent::Attachment {
+                state: attachment.state.to_string(),
+                public_internet_ready: attachment.public_internet_ready,
+            }),
+            Ok(VeilidUpdate::ValueChange(change)) => {
+                let changed_key = change.key.to_string();
+                if let Some((group_id, repo_id)) = find_repo(backend, &changed_key).await {
+                    publish(ServerEvent::DhtValueChange { group_id, repo_id });
+                }
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log_debug!(
+                    TAG,
+                    "Event relay lagged, skipped {} Veilid updates",
+                    skipped
+                );
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Find the joined group owning the repo whose DHT record key is `record_key`.
+async fn find_repo(backend: &Backend, record_key: &str) -> Option<(String, String)> {
+    let groups = backend.list_groups().await.ok()?;
+
+    for group in groups {
+        let repos = group.repos.lock().await;
+        if repos
+            .values()
+            .any(|repo| repo.id().to_string() == record_key)
+        {
+            return Some((group.id().to_string(), record_key.to_string()));
+        }
+    }
+
+    None
+}
+
+#[get("/events")]
+async fn event_stream() -> impl Responder {
+    // Subscribe before reading the current state so no transition slips in between.
+    let receiver = subscribe();
+    let ready = match get_backend().await {
+        Ok(backend) => backend.is_initialized().await,
+        Err(_) => false,
+    };
+
+    let initial = stream::iter(ServerEvent::BackendReady { ready }.to_sse_frame());
+    let events = BroadcastStream::new(receiver).filter_map(|event| async move {
+        match event {
+            Ok(event) => event.to_sse_frame(),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => Some(Bytes::from(format!(
+                "event: lagged\ndata: {{\"skipped\":{skipped}}}\n\n"
+            ))),
+        }
+    });
+    let keepalive = IntervalStream::new(tokio::time::interval(SSE_KEEPALIVE_INTERVAL))
+        .map(|_| Bytes::from_static(b": keepalive\n\n"));
+
+    let body = initial
+        .chain(stream::select(events, keepalive))
+        .take_until(closing())
+        .map(Ok::<_, Infallible>);
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(body)
+}
@@ -0,0 +1,478 @@
+use crate::constants::TAG;
+use crate::error::{ApiError, AppResult};
+use crate::server::{ensure_backend_ready, get_backend};
+use crate::storage;
+use crate::utils::{
+    download_from_peers, find_file_hash, find_group, find_repo, open_decrypted_file, unix_timestamp,
+};
+use crate::{log_error, log_info};
+use actix_web::{
+    get,
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType},
+    web, HttpResponse, Responder,
+};
+use async_zip::tokio::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use bytes::Bytes;
+use futures::AsyncWriteExt as _;
+use futures::{Stream, StreamExt};
+use iroh_blobs::Hash;
+use save_dweb_backend::common::DHTEntity;
+use save_dweb_backend::group::Group;
+use save_dweb_backend::repo::Repo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio_util::io::ReaderStream;
+
+// Bytes buffered between the archive writer and the response body.
+const EXPORT_PIPE_CAPACITY: usize = 256 * 1024;
+
+// Budget for fetching one missing collection or file from peers. Longer than a download
+// request allows, since an export is expected to run for a while.
+const EXPORT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+const MANIFEST_NAME: &str = "manifest.json";
+const FILES_DIR: &str = "files";
+const TAR_BLOCK: usize = 512;
+
+#[derive(Deserialize)]
+pub struct ExportPath {
+    group_id: String,
+    repo_id: Option<String>,
+    format: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    fn parse(format: &str) -> AppResult<Self> {
+        match format {
+            "zip" => Ok(ArchiveFormat::Zip),
+            "tar" => Ok(ArchiveFormat::Tar),
+            _ => Err(ApiError::BadRequest(format!("unsupported export format: {format}")).into()),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::Tar => "application/x-tar",
+        }
+    }
+}
+
+/// Written as the last entry of every export, describing what it holds.
+#[derive(Debug, Serialize)]
+struct Manifest {
+    group_id: String,
+    group_name: Option<String>,
+    exported_at: u64,
+    repos: Vec<ManifestRepo>,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestRepo {
+    repo_id: String,
+    name: String,
+    /// Directory holding the repo's files in the archive.
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestFile {
+    path: String,
+    repo_id: String,
+    name: String,
+    hash: String,
+    /// Plaintext size; `None` for skipped files.
+    size: Option<u64>,
+    /// Why the file is missing from the archive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<String>,
+}
+
+struct PlannedFile {
+    repo: Repo,
+    name: String,
+    hash: Hash,
+    path: String,
+}
+
+/// Files to export, resolved before the response starts so lookup failures of a single
+/// repo export can still be reported as an HTTP error.
+struct ExportPlan {
+    manifest: Manifest,
+    files: Vec<PlannedFile>,
+    used_dirs: HashSet<String>,
+}
+
+impl ExportPlan {
+    async fn new(group: &Group) -> Self {
+        ExportPlan {
+            manifest: Manifest {
+                group_id: group.id().to_string(),
+                group_name: group.get_name().await.ok(),
+                exported_at: unix_timestamp(),
+                repos: Vec::new(),
+                files: Vec::new(),
+            },
+            files: Vec::new(),
+            used_dirs: HashSet::new(),
+        }
+    }
+
+    /// Add every file of `repo`, fetching its collection from peers if needed. On failure
+    /// the repo is listed in the manifest with the error and no files.
+    async fn add_repo(&mut self, group: &Group, repo: Repo) -> AppResult<()> {
+        let name = repo.get_name().await.unwrap_or_default();
+        let dir = self.repo_dir(&name);
+        let mut manifest_repo = ManifestRepo {
+            repo_id: repo.id().to_string(),
+            name,
+            path: format!("{FILES_DIR}/{dir}"),
+            error: None,
+        };
+
+        match repo_files(group, &repo).await {
+            Ok(files) => {
+                // Distinct names can sanitize to the same path, such as `a:b` and `a_b`.
+                let mut used_names = HashSet::new();
+                for (name, hash) in files {
+                    let file_name = unique_file_name(&mut used_names, &sanitize(&name));
+                    self.files.push(PlannedFile {
+                        repo: repo.clone(),
+                        path: format!("{}/{file_name}", manifest_repo.path),
+                        name,
+                        hash,
+                    });
+                }
+                self.manifest.repos.push(manifest_repo);
+                Ok(())
+            }
+            Err(e) => {
+                manifest_repo.error = Some(e.to_string());
+                self.manifest.repos.push(manifest_repo);
+                Err(e)
+            }
+        }
+    }
+
+    /// A directory name for a repo, unique within the archive.
+    fn repo_dir(&mut self, repo_name: &str) -> String {
+        let base = sanitize(repo_name);
+        let mut dir = base.clone();
+        let mut suffix = 2;
+        while !self.used_dirs.insert(dir.clone()) {
+            dir = format!("{base}-{suffix}");
+            suffix += 1;
+        }
+        dir
+    }
+}
+
+/// Stream every file of every repo in the group, one directory per repo.
+#[get("/export.{format}")]
+pub async fn export_group(path: web::Path<ExportPath>) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let path_params = path.into_inner();
+    let format = ArchiveFormat::parse(&path_params.format)?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+
+    let mut plan = ExportPlan::new(&group).await;
+    let repos: Vec<Repo> = group.repos.lock().await.values().cloned().collect();
+    for repo in repos {
+        // Recorded in the manifest; the rest of the group is still exported.
+        if let Err(e) = plan.add_repo(&group, repo).await {
+            log_error!(TAG, "Skipping repo in export: {}", e);
+        }
+    }
+
+    let archive_name = plan.manifest.group_name.clone().unwrap_or_default();
+    Ok(stream_archive(format, &archive_name, group, plan))
+}
+
+/// Stream every file of one repo.
+#[get("/export.{format}")]
+pub async fn export_repo(path: web::Path<ExportPath>) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let path_params = path.into_inner();
+    let format = ArchiveFormat::parse(&path_params.format)?;
+    let repo_id = path_params.repo_id.unwrap_or_default();
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+    let repo = find_repo(&group, &repo_id).await?;
+
+    let archive_name = repo.get_name().await.unwrap_or_default();
+    let mut plan = ExportPlan::new(&group).await;
+    plan.add_repo(&group, *repo).await?;
+
+    Ok(stream_archive(format, &archive_name, group, plan))
+}
+
+/// Names and hashes of a repo's files, fetching another member's collection if needed.
+async fn repo_files(group: &Group, repo: &Repo) -> AppResult<Vec<(String, Hash)>> {
+    if !repo.can_write() {
+        let collection_hash = repo.get_hash_from_dht().await?;
+        if !group.has_hash(&collection_hash).await? {
+            download_from_peers(group, &collection_hash, EXPORT_DOWNLOAD_TIMEOUT).await?;
+        }
+    }
+
+    let mut files = Vec::new();
+    for name in repo.list_files().await? {
+        let hash = find_file_hash(repo, &name).await?;
+        files.push((name, hash));
+    }
+    Ok(files)
+}
+
+fn stream_archive(
+    format: ArchiveFormat,
+    name: &str,
+    group: Box<Group>,
+    plan: ExportPlan,
+) -> HttpResponse {
+    let (writer, reader) = tokio::io::duplex(EXPORT_PIPE_CAPACITY);
+    actix_web::rt::spawn(async move {
+        let file_count = plan.files.len();
+        match write_archive(format, writer, &group, plan).await {
+            Ok(()) => log_info!(TAG, "Exported {} files", file_count),
+            // Also the path taken when the client disconnects mid-download.
+            Err(e) => log_error!(TAG, "Export aborted: {}", e),
+        }
+    });
+
+    let file_name = match sanitize(name).as_str() {
+        "_" => format!("export.{}", format.extension()),
+        name => format!("{name}.{}", format.extension()),
+    };
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .streaming(ReaderStream::new(reader))
+}
+
+async fn write_archive(
+    format: ArchiveFormat,
+    writer: DuplexStream,
+    group: &Group,
+    plan: ExportPlan,
+) -> anyhow::Result<()> {
+    let ExportPlan {
+        mut manifest,
+        files,
+        ..
+    } = plan;
+    let mut archive = ArchiveWriter::new(format, writer);
+
+    for file in files {
+        let mut entry = ManifestFile {
+            path: file.path.clone(),
+            repo_id: file.repo.id().to_string(),
+            name: file.name.clone(),
+            hash: file.hash.to_string(),
+            size: None,
+            skipped: None,
+        };
+        match open_file(group, &file).await {
+            Ok((size, data)) => {
+                archive.append_stream(&file.path, size, data).await?;
+                entry.size = Some(size);
+            }
+            Err(e) => {
+                log_error!(TAG, "Skipping {} in export: {}", file.path, e);
+                entry.skipped = Some(e.to_string());
+            }
+        }
+        manifest.files.push(entry);
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    archive.append_bytes(MANIFEST_NAME, &manifest_json).await?;
+    archive.finish().await
+}
+
+/// The decrypted content of a file, fetching its blob from peers if needed.
+async fn open_file(
+    group: &Group,
+    file: &PlannedFile,
+) -> AppResult<(u64, impl Stream<Item = io::Result<Bytes>>)> {
+    // Held until the file is open so eviction cannot drop the blob after the check.
+    let _reading = storage::blob_write_guard().await;
+    if !group.has_hash(&file.hash).await? {
+        download_from_peers(group, &file.hash, EXPORT_DOWNLOAD_TIMEOUT).await?;
+    }
+    open_decrypted_file(&file.repo, &file.name).await
+}
+
+enum ArchiveWriter {
+    // Entries are stored uncompressed; exported media is mostly compressed already.
+    Zip(ZipFileWriter<DuplexStream>),
+    Tar(DuplexStream),
+}
+
+impl ArchiveWriter {
+    fn new(format: ArchiveFormat, writer: DuplexStream) -> Self {
+        match format {
+            ArchiveFormat::Zip => ArchiveWriter::Zip(ZipFileWriter::with_tokio(writer)),
+            ArchiveFormat::Tar => ArchiveWriter::Tar(writer),
+        }
+    }
+
+    async fn append_stream(
+        &mut self,
+        path: &str,
+        size: u64,
+        data: impl Stream<Item = io::Result<Bytes>>,
+    ) -> anyhow::Result<()> {
+        let mut data = std::pin::pin!(data);
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                let entry = ZipEntryBuilder::new(path.to_string().into(), Compression::Stored);
+                let mut entry_writer = zip.write_entry_stream(entry).await?;
+                while let Some(chunk) = data.next().await {
+                    entry_writer.write_all(&chunk?).await?;
+                }
+                entry_writer.close().await?;
+            }
+            ArchiveWriter::Tar(tar) => {
+                tar.write_all(&tar_header(path, size)?).await?;
+                let mut written: u64 = 0;
+                while let Some(chunk) = data.next().await {
+                    let chunk = chunk?;
+                    written += chunk.len() as u64;
+                    tar.write_all(&chunk).await?;
+                }
+                // The header already promised `size` bytes.
+                if written != size {
+                    anyhow::bail!("{path}: expected {size} bytes, got {written}");
+                }
+                tar.write_all(&tar_padding(size)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn append_bytes(&mut self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                let entry = ZipEntryBuilder::new(path.to_string().into(), Compression::Stored);
+                zip.write_entry_whole(entry, data).await?;
+            }
+            ArchiveWriter::Tar(tar) => {
+                tar.write_all(&tar_header(path, data.len() as u64)?).await?;
+                tar.write_all(data).await?;
+                tar.write_all(&tar_padding(data.len() as u64)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn finish(self) -> anyhow::Result<()> {
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                zip.close().await?.into_inner().shutdown().await?;
+            }
+            ArchiveWriter::Tar(mut tar) => {
+                // A tar archive ends with two empty blocks.
+                tar.write_all(&[0; 2 * TAR_BLOCK]).await?;
+                tar.shutdown().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Header blocks for a regular file, preceded by a GNU long name entry when `path` does
+/// not fit the 100 byte name field.
+fn tar_header(path: &str, size: u64) -> io::Result<Vec<u8>> {
+    let mut blocks = Vec::new();
+    let mut header = tar::Header::new_gnu();
+
+    if path.len() > 100 {
+        let mut long_name = tar::Header::new_gnu();
+        long_name.as_old_mut().name[..13].copy_from_slice(b"././@LongLink");
+        long_name.set_entry_type(tar::EntryType::GNULongName);
+        long_name.set_mode(0o644);
+        long_name.set_size(path.len() as u64 + 1);
+        long_name.set_cksum();
+        blocks.extend_from_slice(long_name.as_bytes());
+        blocks.extend_from_slice(path.as_bytes());
+        blocks.push(0);
+        blocks.extend_from_slice(&tar_padding(path.len() as u64 + 1));
+        header
+            .as_old_mut()
+            .name
+            .copy_from_slice(&path.as_bytes()[..100]);
+    } else {
+        header.set_path(path)?;
+    }
+
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_size(size);
+    header.set_mtime(unix_timestamp());
+    header.set_cksum();
+    blocks.extend_from_slice(header.as_bytes());
+    Ok(blocks)
+}
+
+/// Zeros that fill the last block of an entry of `size` bytes.
+fn tar_padding(size: u64) -> Vec<u8> {
+    let remainder = (size % TAR_BLOCK as u64) as usize;
+    vec![0; (TAR_BLOCK - remainder) % TAR_BLOCK]
+}
+
+/// `name`, or `name` with a numbered suffix before its extension if already in `used`.
+fn unique_file_name(used: &mut HashSet<String>, name: &str) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let mut file_name = name.to_string();
+    let mut suffix = 2;
+    while !used.insert(file_name.clone()) {
+        file_name = format!("{stem}-{suffix}{extension}");
+        suffix += 1;
+    }
+    file_name
+}
+
+/// A single path component safe to extract on any platform. Names come from other
+/// members and must not be able to escape the export directory.
+fn sanitize(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    match sanitized.trim() {
+        "" | "." | ".." => "_".to_string(),
+        _ => sanitized,
+    }
+}
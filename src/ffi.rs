@@ -0,0 +1,146 @@
+//! C entry points for hosts that embed the library without JNI (iOS, desktop shells).
+
+use crate::config::ServerConfig;
+use crate::constants::TAG;
+use crate::server;
+use crate::{log_debug, log_error, log_info};
+use std::ffi::{c_char, CStr, CString};
+use std::time::Duration;
+
+/// Start the server on a background thread. Returns 0 once startup is scheduled, or -1 when
+/// an argument is invalid.
+///
+/// A null `bind_address`, or 0 for `port` or any of the limits, keeps the default from
+/// [`ServerConfig::from_env`]. A null or blank `api_token` makes the server generate one;
+/// read it back with [`save_api_token`].
+///
+/// # Safety
+///
+/// `backend_base_directory` and `server_socket_path` must be valid, NUL-terminated UTF-8
+/// strings. `bind_address` and `api_token` must be either null or such a string.
+#[no_mangle]
+pub unsafe extern "C" fn save_start_server(
+    backend_base_directory: *const c_char,
+    server_socket_path: *const c_char,
+    bind_address: *const c_char,
+    port: u16,
+    enable_tcp: bool,
+    enable_socket: bool,
+    api_token: *const c_char,
+    max_upload_bytes: u64,
+    refresh_concurrency: u32,
+    refresh_deadline_secs: u64,
+) -> i32 {
+    let (Some(backend_base_directory), Some(server_socket_path)) = (
+        c_string(backend_base_directory),
+        c_string(server_socket_path),
+    ) else {
+        log_error!(TAG, "save_start_server: missing or invalid path argument");
+        return -1;
+    };
+
+    let mut config = ServerConfig::from_env();
+    if !bind_address.is_null() {
+        match c_string(bind_address).and_then(|address| address.parse().ok()) {
+            Some(address) => config.bind_address = address,
+            None => {
+                log_error!(TAG, "save_start_server: invalid bind address");
+                return -1;
+            }
+        }
+    }
+    if port != 0 {
+        config.port = port;
+    }
+    config.enable_tcp = enable_tcp;
+    config.enable_socket = enable_socket;
+    if !api_token.is_null() {
+        match c_string(api_token) {
+            Some(token) => config.api_token = Some(token),
+            None => {
+                log_error!(TAG, "save_start_server: invalid api token");
+                return -1;
+            }
+        }
+    }
+    if max_upload_bytes != 0 {
+        config.max_upload_bytes = max_upload_bytes;
+    }
+    if refresh_concurrency != 0 {
+        config.refresh_concurrency = refresh_concurrency as usize;
+    }
+    if refresh_deadline_secs != 0 {
+        config.refresh_deadline = Duration::from_secs(refresh_deadline_secs);
+    }
+    if let Err(e) = config.validate() {
+        log_error!(TAG, "save_start_server: {}", e);
+        return -1;
+    }
+    server::ensure_api_token(&mut config);
+
+    log_debug!(TAG, "save_start_server: {:?}", config);
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            if let Err(e) =
+                server::start_with_config(&backend_base_directory, &server_socket_path, config)
+                    .await
+            {
+                log_error!(TAG, "Server exited with error: {:?}", e);
+            }
+        });
+    });
+
+    0
+}
+
+/// Drain the server and shut down the backend. Returns 0 on success, -1 on error.
+#[no_mangle]
+pub extern "C" fn save_stop_server() -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log_error!(TAG, "save_stop_server: failed to create runtime: {}", e);
+            return -1;
+        }
+    };
+
+    match runtime.block_on(server::shutdown()) {
+        Ok(_) => {
+            log_info!(TAG, "Server stopped successfully");
+            0
+        }
+        Err(e) => {
+            log_error!(TAG, "Error stopping server: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// Bearer token the host must send on `/api` requests, or null before the server starts.
+/// Release the returned string with [`save_free_string`].
+#[no_mangle]
+pub extern "C" fn save_api_token() -> *mut c_char {
+    server::api_token()
+        .and_then(|token| CString::new(token).ok())
+        .map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// Free a string returned by this library.
+///
+/// # Safety
+///
+/// `ptr` must be null or a pointer returned by [`save_api_token`] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn save_free_string(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr));
+    }
+}
+
+unsafe fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    CStr::from_ptr(ptr).to_str().ok().map(str::to_owned)
+}
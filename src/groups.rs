@@ -1,25 +1,54 @@
 use crate::constants::TAG;
-use crate::error::AppResult;
+use crate::error::{ApiError, AppResult};
+use crate::export;
+use crate::history::{self, Change};
+use crate::members;
+use crate::metadata;
 use crate::models::{IntoSnowbirdGroupsWithNames, RequestName, RequestUrl, SnowbirdGroup};
+use crate::pins;
+use crate::qr;
+use crate::redact;
 use crate::repos;
+use crate::storage;
+use crate::sync_state;
 use crate::{log_debug, log_error};
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, http::header, patch, post, web, HttpResponse, Responder};
+use futures::{future, stream, StreamExt};
+use iroh_blobs::Hash;
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
 use crate::server::get_backend;
-use crate::utils::create_veilid_cryptokey_from_base64;
+use crate::utils::{create_veilid_cryptokey_from_base64, find_group, validate_name};
 use save_dweb_backend::common::DHTEntity;
+use save_dweb_backend::group::Group;
+use save_dweb_backend::repo::Repo;
+
+// File hash lookups in flight at once per repo while computing refresh changes.
+const FILE_HASH_CONCURRENCY: usize = 16;
+
+// Largest image `join_from_qr` accepts; full-resolution phone photos fit comfortably.
+const MAX_QR_IMAGE_BYTES: usize = 16 * 1024 * 1024;
 
 pub fn scope() -> actix_web::Scope {
     web::scope("/groups")
         .service(get_groups)
         .service(create_group)
         .service(join_group_from_url)
+        .service(join_group_from_qr)
         .service(
             web::scope("/{group_id}")
                 .service(delete_group)
                 .service(get_group)
+                .service(rename_group)
+                .service(invite)
+                .service(invite_svg)
+                .service(invite_png)
                 .service(refresh_group)
+                .service(export::export_group)
+                .service(members::scope())
+                .service(pins::group_scope())
                 .service(repos::scope()),
         )
 }
@@ -30,8 +59,22 @@ async fn delete_group(group_id: web::Path<String>) -> AppResult<impl Responder>
     let backend = get_backend().await?;
     let group_id = group_id.into_inner();
     let crypto_key = create_veilid_cryptokey_from_base64(&group_id)?;
+    let canonical_group_id = crypto_key.to_string();
 
+    let repo_ids: Vec<String> = match backend.get_group(&crypto_key).await {
+        Ok(group) => group
+            .repos
+            .lock()
+            .await
+            .values()
+            .map(|repo| repo.id().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
     backend.close_group(crypto_key).await?;
+    sync_state::remove(&repo_ids);
+    history::remove(&repo_ids);
+    pins::remove_group(&canonical_group_id);
 
     Ok(HttpResponse::Ok().json(json!({})))
 }
@@ -53,10 +96,7 @@ async fn get_group(group_id: web::Path<String>) -> AppResult<impl Responder> {
     log_debug!(TAG, "got backend");
 
     let group_id = group_id.into_inner();
-    let key = create_veilid_cryptokey_from_base64(group_id.as_str())?;
-    log_debug!(TAG, "got key {}", key);
-
-    let backend_group = backend.get_group(&key).await?;
+    let backend_group = find_group(&backend, &group_id).await?;
     log_debug!(TAG, "got backend group");
 
     let mut snowbird_group: SnowbirdGroup = backend_group.as_ref().into();
@@ -67,6 +107,26 @@ async fn get_group(group_id: web::Path<String>) -> AppResult<impl Responder> {
     Ok(HttpResponse::Ok().json(snowbird_group))
 }
 
+#[patch("")]
+async fn rename_group(
+    group_id: web::Path<String>,
+    request_name: web::Json<RequestName>,
+) -> AppResult<impl Responder> {
+    crate::server::ensure_backend_ready().await?;
+    let name = validate_name(&request_name.name)?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &group_id).await?;
+
+    // Writes the name to the group's DHT record, so other members see it on refresh.
+    group.set_name(&name).await?;
+    log_debug!(TAG, "Renamed group {}", group.id());
+
+    let mut snowbird_group: SnowbirdGroup = group.as_ref().into();
+    snowbird_group.name = Some(name);
+
+    Ok(HttpResponse::Ok().json(snowbird_group))
+}
+
 #[post("")]
 async fn create_group(request_name: web::Json<RequestName>) -> AppResult<impl Responder> {
     let request = request_name.into_inner();
@@ -99,15 +159,43 @@ async fn create_group(request_name: web::Json<RequestName>) -> AppResult<impl Re
 async fn join_group_from_url(request_url: web::Json<RequestUrl>) -> AppResult<impl Responder> {
     let request = request_url.into_inner();
 
-    log_debug!(TAG, "Received request with URL: {:?}", request.url);
+    log_debug!(
+        TAG,
+        "Received request with URL: {}",
+        redact::redact(&request.url)
+    );
+
+    let snowbird_group = join_from_url(&request.url).await?;
+    Ok(HttpResponse::Ok().json(snowbird_group))
+}
+
+/// Join a group from a photo or screenshot of its invite QR code.
+#[post("/join_from_qr")]
+async fn join_group_from_qr(body: web::Payload) -> AppResult<impl Responder> {
+    let image_data = body
+        .to_bytes_limited(MAX_QR_IMAGE_BYTES)
+        .await
+        .map_err(|_| ApiError::PayloadTooLarge(MAX_QR_IMAGE_BYTES as u64))?
+        .map_err(anyhow::Error::new)?;
+    if image_data.is_empty() {
+        return Err(ApiError::BadRequest("Image is empty".to_string()).into());
+    }
 
+    let url = web::block(move || qr::decode(&image_data)).await??;
+    log_debug!(TAG, "Decoded invite QR code");
+
+    let snowbird_group = join_from_url(&url).await?;
+    Ok(HttpResponse::Ok().json(snowbird_group))
+}
+
+async fn join_from_url(url: &str) -> AppResult<SnowbirdGroup> {
     // Ensure backend is fully initialized before proceeding
     crate::server::ensure_backend_ready().await?;
 
     let backend = get_backend().await?;
     log_debug!(TAG, "Obtained backend instance");
 
-    let backend_group = backend.join_from_url(&request.url).await?;
+    let backend_group = backend.join_from_url(url).await?;
     log_debug!(TAG, "Joined backend group successfully");
 
     let mut snowbird_group: SnowbirdGroup = (&*backend_group).into();
@@ -116,7 +204,53 @@ async fn join_group_from_url(request_url: web::Json<RequestUrl>) -> AppResult<im
     snowbird_group.fill_name(backend_group.as_ref()).await;
     log_debug!(TAG, "Filled group name");
 
-    Ok(HttpResponse::Ok().json(snowbird_group))
+    Ok(snowbird_group)
+}
+
+/// The group together with its join URL. The only JSON response that carries the URL.
+#[get("/invite")]
+async fn invite(group_id: web::Path<String>) -> AppResult<impl Responder> {
+    crate::server::ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &group_id).await?;
+
+    let mut snowbird_group: SnowbirdGroup = group.as_ref().into();
+    snowbird_group.fill_name(group.as_ref()).await;
+    snowbird_group.uri = Some(group.get_url()?);
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(snowbird_group))
+}
+
+#[get("/invite.svg")]
+async fn invite_svg(group_id: web::Path<String>) -> AppResult<impl Responder> {
+    let url = invite_url(&group_id).await?;
+    let svg = qr::render_svg(&url)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(svg))
+}
+
+#[get("/invite.png")]
+async fn invite_png(group_id: web::Path<String>) -> AppResult<impl Responder> {
+    let url = invite_url(&group_id).await?;
+    let png = web::block(move || qr::render_png(&url)).await??;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(png))
+}
+
+/// The group's join URL. It embeds the group secret, so responses carrying it are not cached.
+async fn invite_url(group_id: &str) -> AppResult<String> {
+    crate::server::ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, group_id).await?;
+    Ok(group.get_url()?)
 }
 
 #[post("/refresh")]
@@ -137,10 +271,7 @@ async fn refresh_group(group_id: web::Path<String>) -> AppResult<impl Responder>
         }
         Err(e) => {
             log_error!(TAG, "Failed to refresh group from DHT: {}", e);
-            return Ok(HttpResponse::NotFound().json(json!({
-                "status": "error",
-                "error": format!("Group not found: {}", e)
-            })));
+            return Err(ApiError::GroupNotFound(format!("{group_id}: {e}")).into());
         }
     };
     log_debug!(TAG, "Got refreshed group");
@@ -154,170 +285,302 @@ async fn refresh_group(group_id: web::Path<String>) -> AppResult<impl Responder>
     if repos.is_empty() {
         return Ok(HttpResponse::Ok().json(json!({
             "status": "success",
+            "complete": true,
             "refreshed_files": [],
             "repos": []
         })));
     }
 
-    let mut refreshed_repos = Vec::new();
-
-    // For each repo, refresh its collection and files
-    for repo in repos {
-        log_debug!(TAG, "Refreshing repo {}", repo.id());
-
-        let mut repo_info = json!({
-            "repo_id": repo.id().to_string(),
-            "can_write": repo.can_write(),
-            "name": repo.get_name().await.unwrap_or_default(),
-            "refreshed_files": json!(Vec::<String>::new()), // Initialize empty
-            "all_files": json!(Vec::<String>::new())       // Initialize empty
-        });
-        let mut all_files_vec: Vec<String> = Vec::new();
-
-        if repo.can_write() {
-            match tokio::time::timeout(std::time::Duration::from_secs(2), repo.get_hash_from_dht())
-                .await
-            {
-                Ok(Ok(repo_hash)) => {
-                    repo_info["repo_hash"] = json!(repo_hash.to_string());
-                }
-                Ok(Err(e)) => {
-                    log_debug!(TAG, "Error getting repo hash for {}: {}", repo.id(), e);
-                    repo_info["repo_hash_error"] =
-                        json!(format!("Error getting repo hash from DHT: {}", e));
+    let config = crate::server::config();
+    let concurrency = config.refresh_concurrency;
+    let deadline = tokio::time::Instant::now() + config.refresh_deadline;
+    let repo_ids: Vec<String> = repos.iter().map(|repo| repo.id().to_string()).collect();
+    let can_write: Vec<bool> = repos.iter().map(|repo| repo.can_write()).collect();
+    // Read up front so repos still running at the deadline are listed with their names.
+    let names: Vec<String> = future::join_all(
+        repos
+            .iter()
+            .map(|repo| async move { repo.get_name().await.unwrap_or_default() }),
+    )
+    .await;
+
+    // Refresh repos concurrently so one unreachable member does not hold up the rest.
+    let group_ref = group.as_ref();
+    let mut pending =
+        stream::iter(repos.into_iter().zip(names.clone()).enumerate())
+            .map(|(index, (repo, name))| async move {
+                (index, refresh_repo(group_ref, repo, name).await)
+            })
+            .buffer_unordered(concurrency);
+
+    let mut results: Vec<Option<serde_json::Value>> = vec![None; repo_ids.len()];
+    let mut complete = true;
+    loop {
+        match tokio::time::timeout_at(deadline, pending.next()).await {
+            Ok(Some((index, repo_info))) => results[index] = Some(repo_info),
+            Ok(None) => break,
+            Err(_) => {
+                complete = false;
+                break;
+            }
+        }
+    }
+    drop(pending);
+
+    // Keep the original repo order; repos still running at the deadline are marked.
+    let refreshed_repos: Vec<serde_json::Value> = results
+        .into_iter()
+        .enumerate()
+        .map(|(index, repo_info)| {
+            repo_info.unwrap_or_else(|| {
+                log_debug!(TAG, "Refresh deadline reached for repo {}", repo_ids[index]);
+                json!({
+                    "repo_id": repo_ids[index],
+                    "can_write": can_write[index],
+                    "name": names[index],
+                    "refreshed_files": [],
+                    "all_files": [],
+                    "timed_out": true,
+                    "error": "Timed out refreshing repo"
+                })
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "complete": complete,
+        "repos": refreshed_repos
+    })))
+}
+
+/// Refresh one repo's collection metadata, recording failures in the returned JSON.
+async fn refresh_repo(group: &Group, repo: Repo, name: String) -> serde_json::Value {
+    log_debug!(TAG, "Refreshing repo {}", repo.id());
+
+    let mut repo_info = json!({
+        "repo_id": repo.id().to_string(),
+        "can_write": repo.can_write(),
+        "name": name,
+        "refreshed_files": json!(Vec::<String>::new()), // Initialize empty
+        "all_files": json!(Vec::<String>::new()), // Initialize empty
+        "timed_out": false
+    });
+    let mut all_files_vec: Vec<String> = Vec::new();
+
+    if repo.can_write() {
+        let mut collection_hash = None;
+        match tokio::time::timeout(std::time::Duration::from_secs(2), repo.get_hash_from_dht())
+            .await
+        {
+            Ok(Ok(repo_hash)) => {
+                repo_info["repo_hash"] = json!(repo_hash.to_string());
+                collection_hash = Some(repo_hash.to_string());
+            }
+            Ok(Err(e)) => {
+                log_debug!(TAG, "Error getting repo hash for {}: {}", repo.id(), e);
+                repo_info["repo_hash_error"] =
+                    json!(format!("Error getting repo hash from DHT: {}", e));
+            }
+            Err(_) => {
+                log_debug!(
+                    TAG,
+                    "Timed out getting optional writable repo hash for {}",
+                    repo.id()
+                );
+                repo_info["repo_hash_error"] =
+                    json!("Timed out getting optional writable repo hash from DHT");
+            }
+        }
+
+        match repo.list_files().await {
+            Ok(files) => {
+                log_debug!(
+                    TAG,
+                    "Writable repo {} lists local files: {:?}",
+                    repo.id(),
+                    files
+                );
+                repo_info["changes"] = json!(sync_changes(&repo, collection_hash, &files).await);
+                repo_info["all_files"] = json!(files);
+            }
+            Err(e) => {
+                log_debug!(
+                    TAG,
+                    "Error listing local writable repo {}: {}",
+                    repo.id(),
+                    e
+                );
+                repo_info["error_listing_files"] =
+                    json!(format!("Error listing local writable repo files: {}", e));
+            }
+        }
+        return repo_info;
+    }
+
+    // Get current repo hash and collection info
+    match tokio::time::timeout(std::time::Duration::from_secs(30), repo.get_hash_from_dht()).await {
+        Ok(Ok(repo_hash)) => {
+            repo_info["repo_hash"] = json!(repo_hash.to_string());
+
+            // Refresh collection hash if needed
+            log_debug!(
+                TAG,
+                "Repo {} has DHT hash {}. Checking if group has it locally.",
+                repo.id(),
+                repo_hash
+            );
+            let has_collection = match group.has_hash(&repo_hash).await {
+                Ok(has_collection) => has_collection,
+                Err(e) => {
+                    repo_info["error"] = json!(format!("Error checking local collection: {}", e));
+                    return repo_info;
                 }
-                Err(_) => {
-                    log_debug!(
-                        TAG,
-                        "Timed out getting optional writable repo hash for {}",
-                        repo.id()
-                    );
-                    repo_info["repo_hash_error"] =
-                        json!("Timed out getting optional writable repo hash from DHT");
+            };
+            if !has_collection {
+                log_debug!(
+                    TAG,
+                    "Repo {} collection {} not found locally. Downloading...",
+                    repo.id(),
+                    repo_hash
+                );
+                let download = {
+                    let _writing = storage::blob_write_guard().await;
+                    group.download_hash_from_peers(&repo_hash).await
+                };
+                match download {
+                    Ok(_) => {
+                        log_debug!(
+                            TAG,
+                            "Successfully downloaded collection hash {} for repo {}",
+                            repo_hash,
+                            repo.id()
+                        );
+                    }
+                    Err(e) => {
+                        log_debug!(
+                            TAG,
+                            "Error downloading collection hash {} for repo {}: {}",
+                            repo_hash,
+                            repo.id(),
+                            e
+                        );
+                        repo_info["error"] = json!(format!("Error downloading collection: {}", e));
+                        return repo_info; // Skip the file listing if download fails
+                    }
                 }
+            } else {
+                log_debug!(
+                    TAG,
+                    "Repo {} collection {} already local.",
+                    repo.id(),
+                    repo_hash
+                );
             }
 
+            // Now that the collection is ensured to be local, list all files in the repo
             match repo.list_files().await {
                 Ok(files) => {
-                    log_debug!(
-                        TAG,
-                        "Writable repo {} lists local files: {:?}",
-                        repo.id(),
-                        files
-                    );
-                    repo_info["all_files"] = json!(files);
+                    log_debug!(TAG, "Repo {} lists files: {:?}", repo.id(), files);
+                    repo_info["changes"] =
+                        json!(sync_changes(&repo, Some(repo_hash.to_string()), &files).await);
+                    all_files_vec = files;
                 }
                 Err(e) => {
                     log_debug!(
                         TAG,
-                        "Error listing local writable repo {}: {}",
+                        "Error listing files for repo {} after ensuring collection download: {}",
                         repo.id(),
                         e
                     );
+                    // Even if listing fails here, we might have a repo_hash, so continue with empty files.
+                    // Or, handle as a more significant error. For now, log and continue.
                     repo_info["error_listing_files"] =
-                        json!(format!("Error listing local writable repo files: {}", e));
+                        json!(format!("Error listing files post-download: {}", e));
                 }
-            }
-            refreshed_repos.push(repo_info);
-            continue;
+            };
+            repo_info["all_files"] = json!(all_files_vec.clone());
+
+            // Keep refresh metadata-only. Downloading every missing file body here
+            // can block later file discovery behind one slow or failing transfer.
+            // `refreshed_files` is retained for API compatibility; file bodies are
+            // now refreshed only by the explicit media endpoints.
+            log_debug!(
+                TAG,
+                "Repo {} refresh discovered {} files; body downloads are deferred to media endpoint.",
+                repo.id(),
+                all_files_vec.len()
+            );
+            repo_info["refreshed_files"] = json!(Vec::<String>::new());
+        }
+        Ok(Err(e)) => {
+            log_debug!(TAG, "Error getting repo hash for {}: {}", repo.id(), e);
+            repo_info["error"] = json!(format!("Error getting repo hash from DHT: {}", e));
+        }
+        Err(_) => {
+            log_debug!(TAG, "Timed out getting repo hash for {}", repo.id());
+            repo_info["error"] = json!("Timed out getting repo hash from DHT");
         }
+    }
 
-        // Get current repo hash and collection info
-        match tokio::time::timeout(std::time::Duration::from_secs(30), repo.get_hash_from_dht())
-            .await
+    repo_info
+}
+
+/// Diff a repo's files against its previous refresh and remember them for the next one.
+///
+/// Per-file hash lookups are skipped when the collection hash has not moved.
+async fn sync_changes(
+    repo: &Repo,
+    collection_hash: Option<String>,
+    files: &[String],
+) -> sync_state::SyncDiff {
+    let repo_id = repo.id().to_string();
+    let previous = sync_state::last_synced(&repo_id);
+    if let Some(previous) = &previous {
+        if collection_hash.is_some()
+            && previous.collection_hash == collection_hash
+            && previous.files.len() == files.len()
         {
-            Ok(Ok(repo_hash)) => {
-                repo_info["repo_hash"] = json!(repo_hash.to_string());
+            return sync_state::unchanged(previous);
+        }
+    }
 
-                // Refresh collection hash if needed
-                log_debug!(
-                    TAG,
-                    "Repo {} has DHT hash {}. Checking if group has it locally.",
-                    repo.id(),
-                    repo_hash
-                );
-                if !group.has_hash(&repo_hash).await? {
-                    log_debug!(
-                        TAG,
-                        "Repo {} collection {} not found locally. Downloading...",
-                        repo.id(),
-                        repo_hash
-                    );
-                    match group.download_hash_from_peers(&repo_hash).await {
-                        Ok(_) => {
-                            log_debug!(
-                                TAG,
-                                "Successfully downloaded collection hash {} for repo {}",
-                                repo_hash,
-                                repo.id()
-                            );
-                        }
-                        Err(e) => {
-                            log_debug!(
-                                TAG,
-                                "Error downloading collection hash {} for repo {}: {}",
-                                repo_hash,
-                                repo.id(),
-                                e
-                            );
-                            repo_info["error"] =
-                                json!(format!("Error downloading collection: {}", e));
-                            refreshed_repos.push(repo_info);
-                            continue; // Skip to next repo if download fails
-                        }
-                    }
-                } else {
+    let previous = previous.as_ref();
+    let file_hashes: BTreeMap<String, String> = stream::iter(files)
+        .map(|name| async move {
+            match repo.get_file_hash(name).await {
+                Ok(hash) => Some((name.clone(), hash.to_string())),
+                // Keep the last known hash so a failed lookup is not reported as a removal.
+                Err(e) => {
                     log_debug!(
                         TAG,
-                        "Repo {} collection {} already local.",
+                        "Error getting hash of {} in {}: {}",
+                        name,
                         repo.id(),
-                        repo_hash
+                        e
                     );
+                    previous
+                        .and_then(|snapshot| snapshot.files.get(name))
+                        .map(|hash| (name.clone(), hash.clone()))
                 }
-
-                // Now that the collection is ensured to be local, list all files in the repo
-                match repo.list_files().await {
-                    Ok(files) => {
-                        log_debug!(TAG, "Repo {} lists files: {:?}", repo.id(), files);
-                        all_files_vec = files;
-                    }
-                    Err(e) => {
-                        log_debug!(TAG, "Error listing files for repo {} after ensuring collection download: {}", repo.id(), e);
-                        // Even if listing fails here, we might have a repo_hash, so continue with empty files.
-                        // Or, handle as a more significant error. For now, log and continue.
-                        repo_info["error_listing_files"] =
-                            json!(format!("Error listing files post-download: {}", e));
-                    }
-                };
-                repo_info["all_files"] = json!(all_files_vec.clone());
-
-                // Keep refresh metadata-only. Downloading every missing file body here
-                // can block later file discovery behind one slow or failing transfer.
-                // `refreshed_files` is retained for API compatibility; file bodies are
-                // now refreshed only by the explicit media endpoints.
-                log_debug!(
-                    TAG,
-                    "Repo {} refresh discovered {} files; body downloads are deferred to media endpoint.",
-                    repo.id(),
-                    all_files_vec.len()
-                );
-                repo_info["refreshed_files"] = json!(Vec::<String>::new());
-            }
-            Ok(Err(e)) => {
-                log_debug!(TAG, "Error getting repo hash for {}: {}", repo.id(), e);
-                repo_info["error"] = json!(format!("Error getting repo hash from DHT: {}", e));
-            }
-            Err(_) => {
-                log_debug!(TAG, "Timed out getting repo hash for {}", repo.id());
-                repo_info["error"] = json!("Timed out getting repo hash from DHT");
             }
-        }
-
-        refreshed_repos.push(repo_info);
+        })
+        .buffered(FILE_HASH_CONCURRENCY)
+        .filter_map(future::ready)
+        .collect()
+        .await;
+
+    // Keep collections seen for the first time browsable, including other members' ones.
+    if let Some(hash) = collection_hash
+        .as_deref()
+        .and_then(|hash| Hash::from_str(hash).ok())
+    {
+        history::record(&repo_id, &hash, Change::Sync);
     }
-
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "success",
-        "repos": refreshed_repos
-    })))
+    let diff = sync_state::record_sync(&repo_id, collection_hash, file_hashes);
+    // Details recorded for the old content of a changed file no longer apply.
+    for changed in &diff.changed {
+        metadata::forget_if_changed(&repo_id, &changed.name, &changed.current_hash);
+    }
+    diff
 }
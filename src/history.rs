@@ -0,0 +1,333 @@
+use crate::constants::TAG;
+use crate::error::{ApiError, AppResult};
+use crate::log_info;
+use crate::metadata;
+use crate::models::GroupRepoPath;
+use crate::server::get_backend;
+use crate::storage;
+use crate::sync_state::ChangedFile;
+use crate::utils::{
+    download_from_peers, ensure_writable, find_group, find_repo, load_json_state, save_json_state,
+    unix_timestamp, validate_file_name,
+};
+use actix_web::{get, post, web, HttpResponse, Responder, Scope};
+use anyhow::anyhow;
+use iroh_blobs::format::collection::Collection;
+use iroh_blobs::Hash;
+use once_cell::sync::Lazy;
+use save_dweb_backend::common::DHTEntity;
+use save_dweb_backend::group::Group;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const HISTORY_FILE: &str = "history.json";
+
+// Budget for fetching an old collection or file from peers when it is not stored locally.
+const VERSION_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(55);
+
+// Latest versions of each of our own repos whose blobs garbage collection keeps.
+const RETAINED_VERSIONS: usize = 20;
+
+/// What produced a version of a repo's collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Change {
+    Upload {
+        files: Vec<String>,
+    },
+    Delete {
+        file_name: String,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    Restore {
+        file_name: String,
+        from: String,
+    },
+    /// A collection first seen on refresh, usually another member's change.
+    Sync,
+}
+
+/// One collection hash a repo has had, in the order this node saw them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Version {
+    pub collection_hash: String,
+    pub recorded_at: u64,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+// Keyed by repo ID, oldest version first. Entries are only ever appended.
+static HISTORY: Lazy<Mutex<HashMap<String, Vec<Version>>>> =
+    Lazy::new(|| Mutex::new(load_json_state(HISTORY_FILE)));
+
+pub fn scope() -> Scope {
+    web::scope("/history")
+        .service(list_versions)
+        .service(restore_file)
+}
+
+pub fn diff_scope() -> Scope {
+    web::scope("/diff").service(collection_diff)
+}
+
+/// Append a version of `repo_id`, unless it is the collection already recorded last.
+pub fn record(repo_id: &str, collection_hash: &Hash, change: Change) {
+    let Ok(mut history) = HISTORY.lock() else {
+        return;
+    };
+    let collection_hash = collection_hash.to_string();
+    let versions = history.entry(repo_id.to_string()).or_default();
+    if versions
+        .last()
+        .is_some_and(|latest| latest.collection_hash == collection_hash)
+    {
+        return;
+    }
+    versions.push(Version {
+        collection_hash,
+        recorded_at: unix_timestamp(),
+        change,
+    });
+    save_json_state(HISTORY_FILE, &*history);
+}
+
+/// Recorded versions of `repo_id`, oldest first.
+pub fn versions(repo_id: &str) -> Vec<Version> {
+    HISTORY
+        .lock()
+        .ok()
+        .and_then(|history| history.get(repo_id).cloned())
+        .unwrap_or_default()
+}
+
+/// Collection hashes of the latest `RETAINED_VERSIONS` versions of `repo_id`, newest first.
+/// Garbage collection keeps their blobs so they stay restorable without a peer.
+pub fn retained_collections(repo_id: &str) -> Vec<Hash> {
+    versions(repo_id)
+        .iter()
+        .rev()
+        .take(RETAINED_VERSIONS)
+        .filter_map(|version| Hash::from_str(&version.collection_hash).ok())
+        .collect()
+}
+
+/// Forget the history of repos that are no longer tracked.
+pub fn remove(repo_ids: &[String]) {
+    let Ok(mut history) = HISTORY.lock() else {
+        return;
+    };
+    let before = history.len();
+    history.retain(|repo_id, _| !repo_ids.contains(repo_id));
+    if history.len() != before {
+        save_json_state(HISTORY_FILE, &*history);
+    }
+}
+
+/// Parse `at` and check it is a recorded version of `repo_id`.
+pub fn find_version(repo_id: &str, at: &str) -> AppResult<Hash> {
+    let recorded = versions(repo_id)
+        .iter()
+        .any(|version| version.collection_hash == at);
+    match Hash::from_str(at) {
+        Ok(hash) if recorded => Ok(hash),
+        _ => Err(ApiError::VersionNotFound(format!("{at} in repo {repo_id}")).into()),
+    }
+}
+
+/// File names and hashes in an earlier collection, fetching it from peers if needed.
+pub async fn snapshot(group: &Group, collection_hash: &Hash) -> AppResult<Vec<(String, Hash)>> {
+    let backend = get_backend().await?;
+    let store = storage::blob_store(&backend).await?;
+
+    ensure_local(group, collection_hash).await?;
+    // The first link of a collection is the blob holding its file names.
+    let names = storage::hash_seq_links(&store, collection_hash)
+        .await?
+        .and_then(|links| links.first().copied());
+    if let Some(names) = names {
+        ensure_local(group, &names).await?;
+    }
+
+    let collection = Collection::load_db(&store, collection_hash)
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("{collection_hash} is not a collection: {e}")))?;
+    Ok(collection.into_iter().collect())
+}
+
+async fn ensure_local(group: &Group, hash: &Hash) -> AppResult<()> {
+    if !group.has_hash(hash).await? {
+        download_from_peers(group, hash, VERSION_DOWNLOAD_TIMEOUT).await?;
+    }
+    Ok(())
+}
+
+#[get("")]
+async fn list_versions(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
+    let path_params = path.into_inner();
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+
+    let mut versions = versions(&repo.id().to_string());
+    versions.reverse();
+
+    Ok(HttpResponse::Ok().json(json!({ "versions": versions })))
+}
+
+#[derive(Deserialize)]
+struct RestoreRequest {
+    /// Collection hash of the version to restore from.
+    at: String,
+    file_name: String,
+    /// Defaults to `file_name`. An existing file with this name is replaced.
+    name: Option<String>,
+}
+
+/// Put a file from an earlier version back into the repo's current collection.
+#[post("/restore")]
+async fn restore_file(
+    path: web::Path<GroupRepoPath>,
+    body: web::Json<RestoreRequest>,
+) -> AppResult<impl Responder> {
+    let path_params = path.into_inner();
+    let request = body.into_inner();
+    let target_name = request.name.unwrap_or_else(|| request.file_name.clone());
+    validate_file_name(&target_name)?;
+
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+    ensure_writable(&repo)?;
+
+    let repo_key = repo.id().to_string();
+    let collection_hash = find_version(&repo_key, &request.at)?;
+    let file_hash = snapshot(&group, &collection_hash)
+        .await?
+        .into_iter()
+        .find(|(name, _)| *name == request.file_name)
+        .map(|(_, hash)| hash)
+        .ok_or_else(|| {
+            ApiError::FileNotFound(format!("{} in version {}", request.file_name, request.at))
+        })?;
+    ensure_local(&group, &file_hash).await?;
+
+    let _writing = storage::blob_write_guard().await;
+    let updated_collection_hash = repo
+        .update_collection(vec![(target_name.clone(), file_hash)], Vec::new())
+        .await
+        .map_err(|e| anyhow!("Failed to restore file: {e}"))?;
+    // Recorded details describe whatever last had this name; they are learned again on
+    // the next download.
+    metadata::remove(&repo_key, &target_name);
+    record(
+        &repo_key,
+        &updated_collection_hash,
+        Change::Restore {
+            file_name: target_name.clone(),
+            from: request.at,
+        },
+    );
+    log_info!(TAG, "Restored {} in {}", target_name, repo_key);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "name": target_name,
+        "updated_collection_hash": updated_collection_hash,
+        "file_hash": file_hash,
+    })))
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: String,
+    to: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffFile {
+    pub name: String,
+    pub hash: String,
+}
+
+/// File-level differences between two collections of a repo.
+#[derive(Debug, Serialize)]
+pub struct CollectionDiff {
+    pub from: String,
+    pub to: String,
+    pub added: Vec<DiffFile>,
+    pub removed: Vec<DiffFile>,
+    pub modified: Vec<ChangedFile>,
+}
+
+/// Compare two collections file by file. Either may be any collection of the group, not
+/// only a recorded version; missing ones are fetched from peers first.
+#[get("")]
+async fn collection_diff(
+    path: web::Path<GroupRepoPath>,
+    query: web::Query<DiffQuery>,
+) -> AppResult<impl Responder> {
+    let path_params = path.into_inner();
+    let query = query.into_inner();
+    let from = parse_collection_hash(&query.from)?;
+    let to = parse_collection_hash(&query.to)?;
+
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+
+    // Check both hashes before anything is fetched, so only this repo's collections are
+    // ever downloaded from peers.
+    let current = repo.get_hash_from_dht().await.ok();
+    let repo_key = repo.id().to_string();
+    for hash in [&from, &to] {
+        if current.as_ref() != Some(hash) {
+            find_version(&repo_key, &hash.to_string())?;
+        }
+    }
+
+    let from_files: BTreeMap<String, Hash> = snapshot(&group, &from).await?.into_iter().collect();
+    let to_files: BTreeMap<String, Hash> = snapshot(&group, &to).await?.into_iter().collect();
+
+    let mut diff = CollectionDiff {
+        from: from.to_string(),
+        to: to.to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+    };
+    for (name, hash) in &to_files {
+        match from_files.get(name) {
+            None => diff.added.push(DiffFile {
+                name: name.clone(),
+                hash: hash.to_string(),
+            }),
+            Some(previous_hash) if previous_hash != hash => diff.modified.push(ChangedFile {
+                name: name.clone(),
+                previous_hash: previous_hash.to_string(),
+                current_hash: hash.to_string(),
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed = from_files
+        .iter()
+        .filter(|(name, _)| !to_files.contains_key(*name))
+        .map(|(name, hash)| DiffFile {
+            name: name.clone(),
+            hash: hash.to_string(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+fn parse_collection_hash(value: &str) -> AppResult<Hash> {
+    Hash::from_str(value)
+        .map_err(|e| ApiError::BadRequest(format!("invalid collection hash {value}: {e}")).into())
+}
@@ -0,0 +1,289 @@
+use crate::constants::TAG;
+use crate::error::{ApiError, AppResult};
+use crate::events::{self, ServerEvent};
+use crate::metadata;
+use crate::server::get_backend;
+use crate::storage;
+use crate::utils::{
+    download_from_peers, download_with_progress, find_file_hash, find_group, find_repo,
+    unix_timestamp,
+};
+use crate::{log_debug, log_info};
+use actix_web::{delete, get, post, web, HttpResponse, Responder, Scope};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
+
+// Jobs are not bound to an HTTP request, so they can wait far longer for slow peers.
+const JOB_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+// Downloads compete for the same peers and tunnels; keep only a few in flight.
+const MAX_CONCURRENT_JOBS: usize = 2;
+
+// How often a running job's stored bytes are measured and published.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Finished jobs stay queryable for this long before they are pruned.
+const FINISHED_JOB_RETENTION_SECS: u64 = 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobState::Completed | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadJob {
+    pub id: u64,
+    pub group_id: String,
+    pub repo_id: String,
+    pub file_name: String,
+    pub state: JobState,
+    pub bytes_transferred: u64,
+    pub error: Option<String>,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+    #[serde(skip)]
+    abort_handle: Option<AbortHandle>,
+}
+
+static JOBS: Lazy<Mutex<HashMap<u64, DownloadJob>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+static JOB_SLOTS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_JOBS);
+
+pub fn scope() -> Scope {
+    web::scope("/jobs")
+        .service(list_jobs)
+        .service(get_job)
+        .service(retry_job)
+        .service(cancel_job)
+}
+
+/// Queue a background download of one file, reusing an unfinished job for the same file.
+pub fn enqueue_download(group_id: &str, repo_id: &str, file_name: &str) -> DownloadJob {
+    let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+
+    let now = unix_timestamp();
+    jobs.retain(|_, job| {
+        !job.state.is_finished() || now.saturating_sub(job.updated_at) < FINISHED_JOB_RETENTION_SECS
+    });
+
+    if let Some(existing) = jobs.values().find(|job| {
+        !job.state.is_finished()
+            && job.group_id == group_id
+            && job.repo_id == repo_id
+            && job.file_name == file_name
+    }) {
+        return existing.clone();
+    }
+
+    let id = NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed);
+    let job = DownloadJob {
+        id,
+        group_id: group_id.to_string(),
+        repo_id: repo_id.to_string(),
+        file_name: file_name.to_string(),
+        state: JobState::Queued,
+        bytes_transferred: 0,
+        error: None,
+        attempts: 0,
+        created_at: now,
+        updated_at: now,
+        abort_handle: None,
+    };
+    jobs.insert(id, job);
+    start_job(&mut jobs, id);
+
+    jobs[&id].clone()
+}
+
+pub fn get_job_snapshot(id: u64) -> Option<DownloadJob> {
+    JOBS.lock().ok()?.get(&id).cloned()
+}
+
+/// Restart a failed or cancelled job. Returns `None` when the job does not exist.
+pub fn retry(id: u64) -> Option<DownloadJob> {
+    let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    let job = jobs.get_mut(&id)?;
+
+    if matches!(job.state, JobState::Failed | JobState::Cancelled) {
+        job.state = JobState::Queued;
+        job.error = None;
+        job.bytes_transferred = 0;
+        job.updated_at = unix_timestamp();
+        start_job(&mut jobs, id);
+    }
+
+    jobs.get(&id).cloned()
+}
+
+/// Cancel an unfinished job. Returns `None` when the job does not exist.
+pub fn cancel(id: u64) -> Option<DownloadJob> {
+    let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    let job = jobs.get_mut(&id)?;
+
+    if !job.state.is_finished() {
+        if let Some(handle) = job.abort_handle.take() {
+            handle.abort();
+        }
+        job.state = JobState::Cancelled;
+        job.updated_at = unix_timestamp();
+        events::publish(ServerEvent::DownloadProgress(job.clone()));
+        log_info!(TAG, "Cancelled download job {}", id);
+    }
+
+    Some(job.clone())
+}
+
+fn start_job(jobs: &mut HashMap<u64, DownloadJob>, id: u64) {
+    let handle = tokio::spawn(run_job(id));
+    if let Some(job) = jobs.get_mut(&id) {
+        job.abort_handle = Some(handle.abort_handle());
+    }
+}
+
+fn update_job<F>(id: u64, f: F) -> Option<DownloadJob>
+where
+    F: FnOnce(&mut DownloadJob),
+{
+    let mut jobs = JOBS.lock().ok()?;
+    let job = jobs.get_mut(&id)?;
+    f(job);
+    job.updated_at = unix_timestamp();
+    events::publish(ServerEvent::DownloadProgress(job.clone()));
+    Some(job.clone())
+}
+
+async fn run_job(id: u64) {
+    let Ok(_slot) = JOB_SLOTS.acquire().await else {
+        return;
+    };
+
+    let Some(job) = update_job(id, |job| {
+        if job.state != JobState::Queued {
+            return;
+        }
+        job.state = JobState::Running;
+        job.attempts += 1;
+    }) else {
+        return;
+    };
+    if job.state != JobState::Running {
+        return;
+    }
+    log_debug!(
+        TAG,
+        "Running download job {} for {} (attempt {})",
+        id,
+        job.file_name,
+        job.attempts
+    );
+
+    let result = fetch_file(id, &job.group_id, &job.repo_id, &job.file_name).await;
+
+    update_job(id, |job| {
+        job.abort_handle = None;
+        if job.state == JobState::Cancelled {
+            return;
+        }
+        match result {
+            Ok(bytes) => {
+                job.state = JobState::Completed;
+                job.bytes_transferred = bytes;
+            }
+            Err(e) => {
+                job.state = JobState::Failed;
+                job.error = Some(e.to_string());
+            }
+        }
+    });
+}
+
+/// Make sure a file's blob is stored locally, returning its stored size in bytes. Progress
+/// is published on job `id` while the blob downloads.
+async fn fetch_file(id: u64, group_id: &str, repo_id: &str, file_name: &str) -> AppResult<u64> {
+    let backend = get_backend().await?;
+    let group = find_group(&backend, group_id).await?;
+    let repo = find_repo(&group, repo_id).await?;
+
+    if !repo.can_write() {
+        let collection_hash = repo.get_hash_from_dht().await?;
+        if !group.has_hash(&collection_hash).await? {
+            download_from_peers(&group, &collection_hash, JOB_DOWNLOAD_TIMEOUT).await?;
+        }
+    }
+
+    let file_hash = find_file_hash(&repo, file_name).await?;
+    if !group.has_hash(&file_hash).await? {
+        download_with_progress(
+            &group,
+            &file_hash,
+            JOB_DOWNLOAD_TIMEOUT,
+            PROGRESS_INTERVAL,
+            |bytes| {
+                update_job(id, |job| job.bytes_transferred = bytes);
+            },
+        )
+        .await?;
+    }
+
+    let store = storage::blob_store(&backend).await?;
+    let bytes = storage::local_blob_size(&store, &file_hash)
+        .await
+        .ok_or_else(|| anyhow!("{file_hash} is not stored completely after downloading"))?;
+    metadata::record_view(&repo.id().to_string(), file_name, &file_hash);
+
+    Ok(bytes)
+}
+
+#[get("")]
+async fn list_jobs() -> AppResult<impl Responder> {
+    let jobs: Vec<DownloadJob> = JOBS
+        .lock()
+        .map(|jobs| jobs.values().cloned().collect())
+        .unwrap_or_default();
+
+    Ok(HttpResponse::Ok().json(json!({ "jobs": jobs })))
+}
+
+#[get("/{job_id}")]
+async fn get_job(job_id: web::Path<u64>) -> AppResult<impl Responder> {
+    let job_id = job_id.into_inner();
+    let job = get_job_snapshot(job_id).ok_or(ApiError::JobNotFound(job_id))?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[post("/{job_id}/retry")]
+async fn retry_job(job_id: web::Path<u64>) -> AppResult<impl Responder> {
+    let job_id = job_id.into_inner();
+    let job = retry(job_id).ok_or(ApiError::JobNotFound(job_id))?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[delete("/{job_id}")]
+async fn cancel_job(job_id: web::Path<u64>) -> AppResult<impl Responder> {
+    let job_id = job_id.into_inner();
+    let job = cancel(job_id).ok_or(ApiError::JobNotFound(job_id))?;
+    Ok(HttpResponse::Ok().json(job))
+}
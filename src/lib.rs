@@ -5,16 +5,31 @@ pub mod android_bridge;
 #[cfg(target_os = "android")]
 pub mod jni_globals;
 
+pub mod actix_bearer_auth;
+pub mod actix_legacy_errors;
 pub mod actix_route_dumper;
+pub mod config;
 pub mod constants;
 pub mod error;
+pub mod events;
+pub mod export;
+pub mod ffi;
 pub mod logging;
 
 pub mod groups;
+pub mod history;
+pub mod jobs;
 pub mod media;
+pub mod members;
+pub mod metadata;
 pub mod models;
+pub mod pins;
+pub mod qr;
+pub mod redact;
 pub mod repos;
 pub mod server;
+pub mod storage;
+pub mod sync_state;
 pub mod utils;
 
 #[cfg(test)]
@@ -29,6 +44,8 @@ mod tests {
     use base64_url::base64::Engine;
     use models::{RequestName, RequestUrl, SnowbirdFile, SnowbirdGroup, SnowbirdRepo};
     use save_dweb_backend::backend::Backend;
+    use save_dweb_backend::group::Group;
+    use save_dweb_backend::repo::Repo;
     use save_dweb_backend::{common::DHTEntity, constants::TEST_GROUP_NAME};
     use serde::{Deserialize, Serialize};
     use serde_json::json;
@@ -305,6 +322,16 @@ mod tests {
         }
     }
 
+    /// Create a group named `TEST_GROUP_NAME` with one repo named "Test Repo".
+    async fn create_group_with_repo() -> Result<(Group, Repo)> {
+        let backend = server::get_backend().await?;
+        let mut group = backend.create_group().await?;
+        group.set_name(TEST_GROUP_NAME).await?;
+        let repo = group.create_repo().await?;
+        repo.set_name("Test Repo").await?;
+        Ok((group, repo))
+    }
+
     // Helper function to properly clean up test resources
     async fn cleanup_test_resources() -> Result<()> {
         // Get the backend and stop it
@@ -313,8 +340,9 @@ mod tests {
             backend.stop().await?;
         }
 
-        // Clear the backend static
+        // Clear the backend static and any limits a test set
         clear_backend()?;
+        server::set_config(None);
 
         // Add a small delay to ensure everything is cleaned up
         tokio::time::sleep(Duration::from_millis(500)).await;
@@ -322,6 +350,88 @@ mod tests {
         Ok(())
     }
 
+    /// Open `GET /api/events` on a server started with `start_with_config` and wait for
+    /// its first event. Retries the connection until the server listens.
+    fn open_event_stream(port: u16, token: &str) -> std::io::Result<std::net::TcpStream> {
+        use std::io::{Read, Write};
+
+        let mut attempts = 50;
+        let mut stream = loop {
+            match std::net::TcpStream::connect(("127.0.0.1", port)) {
+                Ok(stream) => break stream,
+                Err(e) if attempts == 0 => return Err(e),
+                Err(_) => {
+                    attempts -= 1;
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            }
+        };
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        write!(
+            stream,
+            "GET /api/events HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\n\r\n"
+        )?;
+
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !String::from_utf8_lossy(&received).contains("event: backend_ready") {
+            let read = stream.read(&mut buf)?;
+            if read == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            received.extend_from_slice(&buf[..read]);
+        }
+        Ok(stream)
+    }
+
+    /// Read until the server ends the chunked response or closes the connection. Fails
+    /// with a timeout error if it does neither within the stream's read timeout.
+    fn wait_for_stream_end(mut stream: std::net::TcpStream) -> std::io::Result<()> {
+        use std::io::Read;
+
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let read = stream.read(&mut buf)?;
+            if read == 0 {
+                return Ok(());
+            }
+            received.extend_from_slice(&buf[..read]);
+            if received.ends_with(b"0\r\n\r\n") {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Next event from an SSE response body, skipping keepalive comments. `None` once the
+    /// stream ends.
+    async fn next_sse_event<B>(
+        body: &mut std::pin::Pin<&mut B>,
+    ) -> Option<(String, serde_json::Value)>
+    where
+        B: actix_web::body::MessageBody,
+        B::Error: std::fmt::Debug,
+    {
+        loop {
+            let frame = tokio::time::timeout(
+                Duration::from_secs(30),
+                futures::future::poll_fn(|cx| body.as_mut().poll_next(cx)),
+            )
+            .await
+            .expect("no event within 30 seconds")?
+            .expect("event stream failed");
+            let frame = String::from_utf8(frame.to_vec()).expect("event frame is not UTF-8");
+            if frame.starts_with(':') {
+                continue;
+            }
+            let name = frame
+                .lines()
+                .find_map(|line| line.strip_prefix("event: "))?;
+            let data = frame.lines().find_map(|line| line.strip_prefix("data: "))?;
+            return Some((name.to_string(), serde_json::from_str(data).ok()?));
+        }
+    }
+
     #[actix_web::test]
     #[serial]
     async fn basic_test() -> Result<()> {
@@ -631,7 +741,1415 @@ mod tests {
             "File list should be empty after file deletion"
         );
 
-        // Clean up
+        // Clean up
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_rename_group_and_repo() -> Result<()> {
+        let _path = init_test_backend("test_rename_group_and_repo").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app = test::init_service(
+            App::new()
+                .service(status)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+
+        let rename_group_req = test::TestRequest::patch()
+            .uri(&format!("/api/groups/{}", group.id()))
+            .set_json(RequestName {
+                name: "Renamed Group".to_string(),
+            })
+            .to_request();
+        let renamed_group: SnowbirdGroup =
+            test::call_and_read_body_json(&app, rename_group_req).await;
+        assert_eq!(renamed_group.name.as_deref(), Some("Renamed Group"));
+        assert_eq!(group.get_name().await?, "Renamed Group");
+
+        let rename_repo_req = test::TestRequest::patch()
+            .uri(&format!("/api/groups/{}/repos/{}", group.id(), repo.id()))
+            .set_json(RequestName {
+                name: "Renamed Repo".to_string(),
+            })
+            .to_request();
+        let renamed_repo: SnowbirdRepo = test::call_and_read_body_json(&app, rename_repo_req).await;
+        assert_eq!(renamed_repo.name, "Renamed Repo");
+        assert!(renamed_repo.can_write);
+
+        let list_repos_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}/repos", group.id()))
+            .to_request();
+        let list_repos_resp: ReposResponse =
+            test::call_and_read_body_json(&app, list_repos_req).await;
+        assert_eq!(list_repos_resp.repos[0].name, "Renamed Repo");
+
+        // Blank names are rejected rather than clearing the name.
+        let blank_req = test::TestRequest::patch()
+            .uri(&format!("/api/groups/{}/repos/{}", group.id(), repo.id()))
+            .set_json(RequestName {
+                name: "   ".to_string(),
+            })
+            .to_request();
+        let blank_resp = test::call_service(&app, blank_req).await;
+        assert_eq!(
+            blank_resp.status(),
+            actix_web::http::StatusCode::BAD_REQUEST
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_group_invite_qr_code() -> Result<()> {
+        let _path = init_test_backend("test_group_invite_qr_code").await?;
+
+        let group = {
+            use server::get_backend;
+            let backend = get_backend().await?;
+            let mut group = backend.create_group().await?;
+            group.set_name(TEST_GROUP_NAME).await?;
+            group
+        };
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let svg_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}/invite.svg", group.id()))
+            .to_request();
+        let svg_resp = test::call_service(&app, svg_req).await;
+        assert!(svg_resp.status().is_success());
+        assert_eq!(
+            svg_resp.headers().get("content-type").unwrap(),
+            "image/svg+xml"
+        );
+        let svg = test::read_body(svg_resp).await;
+        assert!(String::from_utf8_lossy(&svg).contains("<svg"));
+
+        // The PNG decodes back to the group's join URL.
+        let png_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}/invite.png", group.id()))
+            .to_request();
+        let png_resp = test::call_service(&app, png_req).await;
+        assert_eq!(png_resp.headers().get("content-type").unwrap(), "image/png");
+        let png = test::read_body(png_resp).await;
+        assert_eq!(
+            qr::decode(&png).expect("invite PNG should decode"),
+            group.get_url()?
+        );
+
+        let bad_req = test::TestRequest::post()
+            .uri("/api/groups/join_from_qr")
+            .set_payload("not an image")
+            .to_request();
+        let bad_resp = test::call_service(&app, bad_req).await;
+        assert_eq!(bad_resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_group_uri_only_in_invite() -> Result<()> {
+        let _path = init_test_backend("test_group_uri_only_in_invite").await?;
+
+        let group = {
+            use server::get_backend;
+            let backend = get_backend().await?;
+            let mut group = backend.create_group().await?;
+            group.set_name(TEST_GROUP_NAME).await?;
+            group
+        };
+        let url = group.get_url()?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let list_req = test::TestRequest::get().uri("/api/groups").to_request();
+        let list_resp: serde_json::Value = test::call_and_read_body_json(&app, list_req).await;
+        assert!(list_resp["groups"][0].get("uri").is_none());
+
+        let get_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}", group.id()))
+            .to_request();
+        let get_resp: serde_json::Value = test::call_and_read_body_json(&app, get_req).await;
+        assert!(get_resp.get("uri").is_none());
+
+        let invite_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}/invite", group.id()))
+            .to_request();
+        let invite_resp = test::call_service(&app, invite_req).await;
+        assert_eq!(
+            invite_resp.headers().get("cache-control").unwrap(),
+            "no-store"
+        );
+        let invite: SnowbirdGroup = test::read_body_json(invite_resp).await;
+        assert_eq!(invite.uri.as_deref(), Some(url.as_str()));
+        assert_eq!(invite.name.as_deref(), Some(TEST_GROUP_NAME));
+
+        // Logged request lines and bodies keep neither the URL nor its secret.
+        let logged = redact::redact_json(&json!({ "uri": url, "name": "Group" }));
+        assert_eq!(logged["uri"], "<redacted>");
+        assert_eq!(logged["name"], "Group");
+        assert!(redact::redact(&url).contains("sk=<redacted>"));
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_list_files_pagination() -> Result<()> {
+        let _path = init_test_backend("test_list_files_pagination").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        for name in ["c.txt", "a.txt", "b.txt"] {
+            repo.upload(name, name.as_bytes().to_vec()).await?;
+        }
+
+        let app = test::init_service(
+            App::new()
+                .service(status)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+
+        let media_uri = format!("/api/groups/{}/repos/{}/media", group.id(), repo.id());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("{media_uri}?sort=name&limit=2"))
+            .to_request();
+        let first_page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(first_page["total"], 3);
+        assert_eq!(first_page["files"][0]["name"], "a.txt");
+        assert_eq!(first_page["files"][1]["name"], "b.txt");
+        let cursor = first_page["next_cursor"]
+            .as_str()
+            .expect("First page should have a next cursor");
+
+        let req = test::TestRequest::get()
+            .uri(&format!("{media_uri}?sort=name&limit=2&cursor={cursor}"))
+            .to_request();
+        let second_page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(second_page["files"].as_array().map(Vec::len), Some(1));
+        assert_eq!(second_page["files"][0]["name"], "c.txt");
+        assert!(second_page["next_cursor"].is_null());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("{media_uri}?sort=name&order=desc&prefix=b"))
+            .to_request();
+        let filtered: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(filtered["total"], 1);
+        assert_eq!(filtered["files"][0]["name"], "b.txt");
+
+        // Our own uploads are always local.
+        let req = test::TestRequest::get()
+            .uri(&format!("{media_uri}?downloaded=false"))
+            .to_request();
+        let not_downloaded: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(not_downloaded["total"], 0);
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_upload_rejects_oversized_body() -> Result<()> {
+        let _path = init_test_backend("test_upload_oversized").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app = test::init_service(
+            App::new()
+                .service(status)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+
+        // Cap uploads well below the payload size so the body is rejected mid-stream.
+        server::set_config(Some(config::ServerConfig {
+            max_upload_bytes: 1024,
+            ..config::ServerConfig::default()
+        }));
+        let upload_req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/too_big.bin",
+                group.id(),
+                repo.id()
+            ))
+            .set_payload(deterministic_test_payload(64 * 1024))
+            .to_request();
+        let upload_resp = test::call_service(&app, upload_req).await;
+        server::set_config(None);
+
+        assert_eq!(
+            upload_resp.status(),
+            actix_web::http::StatusCode::PAYLOAD_TOO_LARGE,
+            "Oversized upload should be rejected with 413"
+        );
+
+        let list_files_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let list_files_resp: FilesResponse =
+            test::call_and_read_body_json(&app, list_files_req).await;
+        assert!(
+            list_files_resp.files.is_empty(),
+            "Rejected upload should not be committed to the repo"
+        );
+
+        // An empty body is the client's mistake, not a server failure.
+        let empty_req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/empty.bin",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let empty_resp = test::call_service(&app, empty_req).await;
+        assert_eq!(
+            empty_resp.status(),
+            actix_web::http::StatusCode::BAD_REQUEST
+        );
+        let empty_body: serde_json::Value = test::read_body_json(empty_resp).await;
+        assert_eq!(empty_body["code"], "bad_request");
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_batch_upload() -> Result<()> {
+        let _path = init_test_backend("test_batch_upload").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let boundary = "save-batch-boundary";
+        let batch_upload = |files: &[(&str, &[u8])]| {
+            let mut body = Vec::new();
+            for (file_name, content) in files {
+                body.extend_from_slice(
+                    format!(
+                        "--{boundary}\r\nContent-Disposition: form-data; name=\"files\"; \
+                         filename=\"{file_name}\"\r\nContent-Type: text/plain\r\n\r\n"
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(content);
+                body.extend_from_slice(b"\r\n");
+            }
+            body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+            test::TestRequest::post()
+                .uri(&format!(
+                    "/api/groups/{}/repos/{}/media",
+                    group.id(),
+                    repo.id()
+                ))
+                .insert_header((
+                    "content-type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                ))
+                .set_payload(body)
+                .to_request()
+        };
+
+        let upload_req = batch_upload(&[
+            ("first.txt", b"first file"),
+            ("empty.txt", b""),
+            ("second.txt", b"second file"),
+        ]);
+        let upload_resp: serde_json::Value = test::call_and_read_body_json(&app, upload_req).await;
+
+        let files = upload_resp["files"].as_array().unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(files[0]["file_hash"].is_string());
+        assert_eq!(files[1]["name"], "empty.txt");
+        assert!(files[1]["error"].is_string());
+        assert!(files[2]["file_hash"].is_string());
+        assert_eq!(
+            upload_resp["updated_collection_hash"].as_str(),
+            Some(repo.get_hash_from_dht().await?.to_string().as_str())
+        );
+
+        // Both files landed in one commit; the failed one was left out.
+        let mut listed = repo.list_files().await?;
+        listed.sort();
+        assert_eq!(listed, vec!["first.txt", "second.txt"]);
+
+        // A rejected duplicate is still read, so its bytes count toward the cap.
+        server::set_config(Some(config::ServerConfig {
+            max_upload_bytes: 64,
+            ..config::ServerConfig::default()
+        }));
+        let over_cap_req = batch_upload(&[("dup.txt", &[b'a'; 40]), ("dup.txt", &[b'b'; 40])]);
+        let over_cap_resp = test::call_service(&app, over_cap_req).await;
+        server::set_config(None);
+        assert_eq!(
+            over_cap_resp.status(),
+            actix_web::http::StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert!(!repo.list_files().await?.contains(&"dup.txt".to_string()));
+
+        // A body that is not multipart gets the usual JSON error.
+        let json_req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media",
+                group.id(),
+                repo.id()
+            ))
+            .set_json(json!({ "name": "not-a-file.txt" }))
+            .to_request();
+        let json_resp = test::call_service(&app, json_req).await;
+        assert_eq!(json_resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let json_body: serde_json::Value = test::read_body_json(json_resp).await;
+        assert_eq!(json_body["code"], "bad_request");
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_rename_and_copy_file() -> Result<()> {
+        let _path = init_test_backend("test_rename_and_copy_file").await?;
+
+        let (group, repo, other_group, other_repo) = {
+            use server::get_backend;
+            let backend = get_backend().await?;
+            let mut group = backend.create_group().await?;
+            group.set_name(TEST_GROUP_NAME).await?;
+            let repo = group.create_repo().await?;
+            repo.set_name("Test Repo").await?;
+            repo.upload("draft.txt", b"file content".to_vec()).await?;
+            let mut other_group = backend.create_group().await?;
+            other_group.set_name("Other Group").await?;
+            let other_repo = other_group.create_repo().await?;
+            other_repo.set_name("Other Repo").await?;
+            (group, repo, other_group, other_repo)
+        };
+        let file_hash = repo.get_file_hash("draft.txt").await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let rename_req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/draft.txt/rename",
+                group.id(),
+                repo.id()
+            ))
+            .set_json(json!({ "name": "final.txt" }))
+            .to_request();
+        let renamed: serde_json::Value = test::call_and_read_body_json(&app, rename_req).await;
+        assert_eq!(renamed["name"], "final.txt");
+        // The blob is reused, not uploaded again.
+        assert_eq!(renamed["file_hash"], json!(file_hash));
+        assert_eq!(repo.list_files().await?, vec!["final.txt"]);
+
+        let copy_req = || {
+            test::TestRequest::post()
+                .uri(&format!(
+                    "/api/groups/{}/repos/{}/media/final.txt/copy",
+                    group.id(),
+                    repo.id()
+                ))
+                .set_json(json!({
+                    "group_id": other_group.id().to_string(),
+                    "repo_id": other_repo.id().to_string(),
+                }))
+                .to_request()
+        };
+        let copy_resp = test::call_service(&app, copy_req()).await;
+        assert!(copy_resp.status().is_success());
+
+        let download_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/final.txt",
+                other_group.id(),
+                other_repo.id()
+            ))
+            .to_request();
+        let copied = test::call_and_read_body(&app, download_req).await;
+        assert_eq!(copied.as_ref(), b"file content");
+
+        // The target name is now taken.
+        let conflict_resp = test::call_service(&app, copy_req()).await;
+        assert_eq!(
+            conflict_resp.status(),
+            actix_web::http::StatusCode::CONFLICT
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_history_and_restore() -> Result<()> {
+        let _path = init_test_backend("test_history_and_restore").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let media_uri = format!("/api/groups/{}/repos/{}/media", group.id(), repo.id());
+        let history_uri = format!("/api/groups/{}/repos/{}/history", group.id(), repo.id());
+
+        // Upload, overwrite, then delete the same file.
+        for content in ["first draft", "second draft"] {
+            let upload_req = test::TestRequest::post()
+                .uri(&format!("{media_uri}/notes.txt"))
+                .set_payload(content)
+                .to_request();
+            let upload_resp = test::call_service(&app, upload_req).await;
+            assert!(upload_resp.status().is_success());
+        }
+        let delete_req = test::TestRequest::delete()
+            .uri(&format!("{media_uri}/notes.txt"))
+            .to_request();
+        assert!(test::call_service(&app, delete_req)
+            .await
+            .status()
+            .is_success());
+
+        let history_req = test::TestRequest::get().uri(&history_uri).to_request();
+        let history: serde_json::Value = test::call_and_read_body_json(&app, history_req).await;
+        let versions = history["versions"].as_array().unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0]["action"], "delete");
+        assert_eq!(versions[2]["action"], "upload");
+        let first_version = versions[2]["collection_hash"].as_str().unwrap().to_string();
+
+        // The first version still lists the deleted file.
+        let snapshot_req = test::TestRequest::get()
+            .uri(&format!("{media_uri}?at={first_version}"))
+            .to_request();
+        let snapshot: FilesResponse = test::call_and_read_body_json(&app, snapshot_req).await;
+        assert_eq!(snapshot.files.len(), 1);
+        assert_eq!(snapshot.files[0].name, "notes.txt");
+
+        let restore_req = test::TestRequest::post()
+            .uri(&format!("{history_uri}/restore"))
+            .set_json(json!({ "at": first_version, "file_name": "notes.txt" }))
+            .to_request();
+        let restore_resp = test::call_service(&app, restore_req).await;
+        assert!(restore_resp.status().is_success());
+
+        let download_req = test::TestRequest::get()
+            .uri(&format!("{media_uri}/notes.txt"))
+            .to_request();
+        let restored = test::call_and_read_body(&app, download_req).await;
+        assert_eq!(restored.as_ref(), b"first draft");
+
+        // Only recorded versions can be browsed.
+        let unknown_req = test::TestRequest::get()
+            .uri(&format!("{media_uri}?at=not-a-version"))
+            .to_request();
+        let unknown_resp = test::call_service(&app, unknown_req).await;
+        assert_eq!(
+            unknown_resp.status(),
+            actix_web::http::StatusCode::NOT_FOUND
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_collection_diff() -> Result<()> {
+        let _path = init_test_backend("test_collection_diff").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let media_uri = format!("/api/groups/{}/repos/{}/media", group.id(), repo.id());
+        let upload = |file_name: &str, content: &'static str| {
+            test::TestRequest::post()
+                .uri(&format!("{media_uri}/{file_name}"))
+                .set_payload(content)
+                .to_request()
+        };
+
+        test::call_service(&app, upload("kept.txt", "kept")).await;
+        test::call_service(&app, upload("edited.txt", "before")).await;
+        test::call_service(&app, upload("removed.txt", "removed")).await;
+        let from = repo.get_hash_from_dht().await?;
+        let removed_hash = repo.get_file_hash("removed.txt").await?;
+
+        test::call_service(&app, upload("edited.txt", "after")).await;
+        test::call_service(&app, upload("added.txt", "added")).await;
+        let delete_req = test::TestRequest::delete()
+            .uri(&format!("{media_uri}/removed.txt"))
+            .to_request();
+        test::call_service(&app, delete_req).await;
+        let to = repo.get_hash_from_dht().await?;
+
+        let diff_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/diff?from={from}&to={to}",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let diff: serde_json::Value = test::call_and_read_body_json(&app, diff_req).await;
+
+        assert_eq!(diff["added"][0]["name"], "added.txt");
+        assert_eq!(diff["added"].as_array().unwrap().len(), 1);
+        assert_eq!(diff["removed"][0]["name"], "removed.txt");
+        assert_eq!(diff["removed"][0]["hash"], removed_hash.to_string());
+        assert_eq!(diff["modified"][0]["name"], "edited.txt");
+        assert_eq!(diff["modified"].as_array().unwrap().len(), 1);
+
+        let bad_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/diff?from=nonsense&to={to}",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let bad_resp = test::call_service(&app, bad_req).await;
+        assert_eq!(bad_resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        // A blob hash that is not one of the repo's collections is refused up front.
+        let unknown_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/diff?from={removed_hash}&to={to}",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let unknown_resp = test::call_service(&app, unknown_req).await;
+        assert_eq!(
+            unknown_resp.status(),
+            actix_web::http::StatusCode::NOT_FOUND
+        );
+        let unknown_body: serde_json::Value = test::read_body_json(unknown_resp).await;
+        assert_eq!(unknown_body["code"], "version_not_found");
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_export_archives() -> Result<()> {
+        let _path = init_test_backend("test_export_archives").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        repo.upload("evidence.txt", b"evidence content".to_vec())
+            .await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let tar_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/export.tar",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let tar_resp = test::call_service(&app, tar_req).await;
+        assert!(tar_resp.status().is_success());
+        assert_eq!(
+            tar_resp.headers().get("content-type").unwrap(),
+            "application/x-tar"
+        );
+        let tar_body = test::read_body(tar_resp).await;
+
+        let mut entries = Vec::new();
+        let mut archive = tar::Archive::new(tar_body.as_ref());
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut content = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut content)?;
+            entries.push((path, content));
+        }
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, "files/Test Repo/evidence.txt");
+        assert_eq!(entries[0].1, b"evidence content");
+
+        // The manifest comes last and lists the file with its hash and size.
+        assert_eq!(entries[1].0, "manifest.json");
+        let manifest: serde_json::Value = serde_json::from_slice(&entries[1].1)?;
+        assert_eq!(manifest["files"][0]["name"], "evidence.txt");
+        assert_eq!(
+            manifest["files"][0]["hash"],
+            repo.get_file_hash("evidence.txt").await?.to_string()
+        );
+        assert_eq!(manifest["files"][0]["size"], 16);
+
+        // Names that sanitize to the same path are exported side by side.
+        repo.upload("notes:1.txt", b"first notes".to_vec()).await?;
+        repo.upload("notes_1.txt", b"second notes".to_vec()).await?;
+        let tar_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/export.tar",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let tar_body = test::call_and_read_body(&app, tar_req).await;
+        let mut notes = Vec::new();
+        let mut archive = tar::Archive::new(tar_body.as_ref());
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut content)?;
+            if path.starts_with("files/Test Repo/notes_1") {
+                notes.push((path, content));
+            }
+        }
+        notes.sort();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].0, "files/Test Repo/notes_1-2.txt");
+        assert_eq!(notes[1].0, "files/Test Repo/notes_1.txt");
+        assert_ne!(notes[0].1, notes[1].1);
+
+        let zip_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}/export.zip", group.id()))
+            .to_request();
+        let zip_resp = test::call_service(&app, zip_req).await;
+        assert!(zip_resp.status().is_success());
+        let zip_body = test::read_body(zip_resp).await;
+        assert!(zip_body.starts_with(b"PK\x03\x04"));
+        // Entries are stored uncompressed, so the names and content are visible as is.
+        let zip_text = String::from_utf8_lossy(&zip_body);
+        assert!(zip_text.contains("evidence content"));
+        assert!(zip_text.contains("manifest.json"));
+
+        let bad_req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{}/export.rar", group.id()))
+            .to_request();
+        let bad_resp = test::call_service(&app, bad_req).await;
+        assert_eq!(bad_resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_download_byte_range() -> Result<()> {
+        let _path = init_test_backend("test_download_byte_range").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let file_name = "range.bin";
+        let file_content = deterministic_test_payload(256 * 1024);
+        repo.upload(file_name, file_content.clone()).await?;
+
+        let app = test::init_service(
+            App::new()
+                .service(status)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+        let file_uri = format!(
+            "/api/groups/{}/repos/{}/media/{}",
+            group.id(),
+            repo.id(),
+            file_name
+        );
+
+        // A plain GET advertises range support and returns the whole body.
+        let full_req = test::TestRequest::get().uri(&file_uri).to_request();
+        let full_resp = test::call_service(&app, full_req).await;
+        assert!(full_resp.status().is_success(), "Full download failed");
+        assert_eq!(
+            full_resp
+                .headers()
+                .get(actix_web::http::header::ACCEPT_RANGES)
+                .and_then(|value| value.to_str().ok()),
+            Some("bytes"),
+            "Downloads should advertise byte range support"
+        );
+        let full_body = test::read_body(full_resp).await;
+        assert_eq!(full_body.to_vec(), file_content, "Full body should match");
+
+        // A bounded range returns 206 with just the requested slice.
+        let range_req = test::TestRequest::get()
+            .uri(&file_uri)
+            .insert_header(("Range", "bytes=70000-70099"))
+            .to_request();
+        let range_resp = test::call_service(&app, range_req).await;
+        assert_eq!(
+            range_resp.status(),
+            actix_web::http::StatusCode::PARTIAL_CONTENT,
+            "Range request should return 206"
+        );
+        assert_eq!(
+            range_resp
+                .headers()
+                .get(actix_web::http::header::CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok()),
+            Some(format!("bytes 70000-70099/{}", file_content.len()).as_str())
+        );
+        let range_body = test::read_body(range_resp).await;
+        assert_eq!(range_body.to_vec(), file_content[70000..70100].to_vec());
+
+        // A suffix range returns the tail of the file.
+        let suffix_req = test::TestRequest::get()
+            .uri(&file_uri)
+            .insert_header(("Range", "bytes=-10"))
+            .to_request();
+        let suffix_body = test::call_and_read_body(&app, suffix_req).await;
+        assert_eq!(
+            suffix_body.to_vec(),
+            file_content[file_content.len() - 10..].to_vec()
+        );
+
+        // A range past the end is unsatisfiable.
+        let bad_range_req = test::TestRequest::get()
+            .uri(&file_uri)
+            .insert_header(("Range", format!("bytes={}-", file_content.len())))
+            .to_request();
+        let bad_range_resp = test::call_service(&app, bad_range_req).await;
+        assert_eq!(
+            bad_range_resp.status(),
+            actix_web::http::StatusCode::RANGE_NOT_SATISFIABLE
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_open_decrypted_file_errors() -> Result<()> {
+        use iroh_blobs::store::Store;
+
+        let _path = init_test_backend("test_open_decrypted_file_errors").await?;
+
+        let (_group, repo) = create_group_with_repo().await?;
+
+        // A blob that was never encrypted, as a corrupted or foreign file would be.
+        let backend = server::get_backend().await?;
+        let store = storage::blob_store(&backend).await.map_err(|e| e.0)?;
+        let tag = store
+            .import_bytes(
+                bytes::Bytes::from_static(b"not encrypted"),
+                iroh_blobs::BlobFormat::Raw,
+            )
+            .await?;
+        repo.update_collection(vec![("plain.bin".to_string(), *tag.hash())], Vec::new())
+            .await?;
+
+        // Pins the backend message `open_decrypted_file` classifies.
+        let Err(error) = utils::open_decrypted_file(&repo, "plain.bin").await else {
+            panic!("An unencrypted blob should not decrypt");
+        };
+        assert_eq!(
+            error.api_error().map(error::ApiError::code),
+            Some("decrypt_failure"),
+            "Unexpected error: {error}"
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_media_metadata_and_content_type() -> Result<()> {
+        let _path = init_test_backend("test_media_metadata").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app = test::init_service(
+            App::new()
+                .service(status)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+
+        // JPEG signature behind a misleading extension: content sniffing should win.
+        let file_name = "capture.bin";
+        let mut file_content = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        file_content.extend(deterministic_test_payload(1024));
+        let media_uri = format!("/api/groups/{}/repos/{}/media", group.id(), repo.id());
+
+        let upload_req = test::TestRequest::post()
+            .uri(&format!("{media_uri}/{file_name}"))
+            .set_payload(file_content.clone())
+            .to_request();
+        let upload_resp = test::call_service(&app, upload_req).await;
+        assert!(upload_resp.status().is_success(), "File upload failed");
+
+        let list_req = test::TestRequest::get().uri(&media_uri).to_request();
+        let list_resp: FilesResponse = test::call_and_read_body_json(&app, list_req).await;
+        let listed = list_resp
+            .files
+            .iter()
+            .find(|file| file.name == file_name)
+            .expect("Uploaded file should be listed");
+        assert_eq!(listed.mime_type.as_deref(), Some("image/jpeg"));
+        assert_eq!(listed.size, Some(file_content.len() as u64));
+        assert!(
+            listed.uploaded_at.is_some(),
+            "Upload time should be recorded"
+        );
+
+        let get_req = test::TestRequest::get()
+            .uri(&format!("{media_uri}/{file_name}"))
+            .to_request();
+        let get_resp = test::call_service(&app, get_req).await;
+        assert!(get_resp.status().is_success(), "File download failed");
+        let header_value = |name: actix_web::http::header::HeaderName| {
+            get_resp
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        assert_eq!(
+            header_value(actix_web::http::header::CONTENT_TYPE).as_deref(),
+            Some("image/jpeg")
+        );
+        let disposition = header_value(actix_web::http::header::CONTENT_DISPOSITION)
+            .expect("Download should carry a Content-Disposition header");
+        assert!(
+            disposition.starts_with("inline") && disposition.contains(file_name),
+            "Unexpected Content-Disposition: {disposition}"
+        );
+
+        // Overwrite the content without going through this API, as another member's
+        // device would. The recorded JPEG details must not be served for the new content.
+        let mut png_content = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        png_content.extend(deterministic_test_payload(2048));
+        repo.upload(file_name, png_content.clone()).await?;
+
+        let get_req = test::TestRequest::get()
+            .uri(&format!("{media_uri}/{file_name}"))
+            .to_request();
+        let get_resp = test::call_service(&app, get_req).await;
+        assert_eq!(
+            get_resp
+                .headers()
+                .get(actix_web::http::header::CONTENT_TYPE)
+                .unwrap(),
+            "image/png"
+        );
+        let got = test::read_body(get_resp).await;
+        assert_eq!(got.to_vec(), png_content);
+
+        let list_req = test::TestRequest::get().uri(&media_uri).to_request();
+        let list_resp: FilesResponse = test::call_and_read_body_json(&app, list_req).await;
+        let listed = list_resp
+            .files
+            .iter()
+            .find(|file| file.name == file_name)
+            .expect("Overwritten file should be listed");
+        assert_eq!(listed.mime_type.as_deref(), Some("image/png"));
+        assert_eq!(listed.size, Some(png_content.len() as u64));
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_fetch_job_completes() -> Result<()> {
+        let _path = init_test_backend("test_fetch_job_completes").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let file_name = "job.txt";
+        repo.upload(file_name, b"job content".to_vec()).await?;
+
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .service(groups::scope())
+                    .service(jobs::scope()),
+            ),
+        )
+        .await;
+
+        let fetch_req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/{}/fetch",
+                group.id(),
+                repo.id(),
+                file_name
+            ))
+            .to_request();
+        let fetch_resp = test::call_service(&app, fetch_req).await;
+        assert_eq!(
+            fetch_resp.status(),
+            actix_web::http::StatusCode::ACCEPTED,
+            "Fetch should enqueue a job"
+        );
+        let job: serde_json::Value = test::read_body_json(fetch_resp).await;
+        let job_id = job["id"].as_u64().expect("job id should be a number");
+
+        // The file is already local, so the job should finish quickly.
+        let mut retries = 20;
+        let job = loop {
+            let job_req = test::TestRequest::get()
+                .uri(&format!("/api/jobs/{job_id}"))
+                .to_request();
+            let job: serde_json::Value = test::call_and_read_body_json(&app, job_req).await;
+            if job["state"] == "completed" || job["state"] == "failed" {
+                break job;
+            }
+            retries -= 1;
+            if retries == 0 {
+                panic!("Job did not finish: {job}");
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        };
+        assert_eq!(job["state"], "completed", "Job should complete: {job}");
+        assert!(job["bytes_transferred"].as_u64().unwrap_or(0) > 0);
+
+        let missing_req = test::TestRequest::get()
+            .uri("/api/jobs/999999")
+            .to_request();
+        let missing_resp = test::call_service(&app, missing_req).await;
+        assert_eq!(
+            missing_resp.status(),
+            actix_web::http::StatusCode::NOT_FOUND
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_pin_repo() -> Result<()> {
+        let _path = init_test_backend("test_pin_repo").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        repo.upload("pinned.txt", b"pinned content".to_vec())
+            .await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+        let pin_uri = format!("/api/groups/{}/repos/{}/pin", group.id(), repo.id());
+
+        let pin_req = test::TestRequest::post().uri(&pin_uri).to_request();
+        let pin_resp = test::call_service(&app, pin_req).await;
+        assert_eq!(pin_resp.status(), actix_web::http::StatusCode::ACCEPTED);
+        let pin: serde_json::Value = test::read_body_json(pin_resp).await;
+        assert_eq!(pin["repo_id"], repo.id().to_string());
+
+        // Our own files are already local, so the first sync has nothing to download.
+        let mut retries = 20;
+        let pin = loop {
+            let status_req = test::TestRequest::get().uri(&pin_uri).to_request();
+            let pin: serde_json::Value = test::call_and_read_body_json(&app, status_req).await;
+            if pin["state"] != "syncing" {
+                break pin;
+            }
+            retries -= 1;
+            if retries == 0 {
+                panic!("Pin did not finish syncing: {pin}");
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        };
+        assert_eq!(pin["state"], "synced", "Pin should sync: {pin}");
+        assert_eq!(pin["total_files"], 1);
+        assert_eq!(pin["missing_files"], 0);
+        assert_eq!(pin["missing_bytes"], 0);
+
+        let unpin_req = test::TestRequest::delete().uri(&pin_uri).to_request();
+        let unpin_resp = test::call_service(&app, unpin_req).await;
+        assert!(unpin_resp.status().is_success());
+
+        let status_req = test::TestRequest::get().uri(&pin_uri).to_request();
+        let status_resp = test::call_service(&app, status_req).await;
+        assert_eq!(status_resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+        let body: serde_json::Value = test::read_body_json(status_resp).await;
+        assert_eq!(body["code"], "pin_not_found");
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_storage_report_and_gc() -> Result<()> {
+        let _path = init_test_backend("test_storage_report_and_gc").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        repo.upload("keep.txt", b"keep me".to_vec()).await?;
+        repo.upload("drop.txt", b"drop me".to_vec()).await?;
+        repo.delete_file("drop.txt").await?;
+
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .service(groups::scope())
+                    .service(storage::scope()),
+            ),
+        )
+        .await;
+
+        let report_req = test::TestRequest::get().uri("/api/storage").to_request();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, report_req).await;
+        assert_eq!(
+            report["complete"], true,
+            "Report should be complete: {report}"
+        );
+        let group_usage = report["groups"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|g| g["group_id"] == group.id().to_string())
+            .expect("group should be in the report");
+        assert_eq!(group_usage["repos"][0]["local_files"], 1);
+        assert!(group_usage["local_bytes"].as_u64().unwrap() > 0);
+        // The deleted file and the superseded collections are no longer referenced.
+        assert!(report["orphaned"]["blobs"].as_u64().unwrap() > 0);
+
+        let gc_req = test::TestRequest::post()
+            .uri("/api/storage/gc")
+            .to_request();
+        let gc: serde_json::Value = test::call_and_read_body_json(&app, gc_req).await;
+        assert_eq!(gc["deleted_blobs"], report["orphaned"]["blobs"]);
+        assert_eq!(gc["freed_bytes"], report["orphaned"]["bytes"]);
+
+        let report_req = test::TestRequest::get().uri("/api/storage").to_request();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, report_req).await;
+        assert_eq!(report["orphaned"]["blobs"], 0);
+
+        // Files still in the collection survive garbage collection.
+        let get_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/keep.txt",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let got = test::call_and_read_body(&app, get_req).await;
+        assert_eq!(got.to_vec(), b"keep me".to_vec());
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_gc_keeps_deleted_files_restorable() -> Result<()> {
+        let _path = init_test_backend("test_gc_keeps_deleted_files_restorable").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .service(groups::scope())
+                    .service(storage::scope()),
+            ),
+        )
+        .await;
+
+        let payload = deterministic_test_payload(256 * 1024);
+        let media_uri = format!(
+            "/api/groups/{}/repos/{}/media/deleted.bin",
+            group.id(),
+            repo.id()
+        );
+        let upload_req = test::TestRequest::post()
+            .uri(&media_uri)
+            .set_payload(payload.clone())
+            .to_request();
+        let upload_resp = test::call_service(&app, upload_req).await;
+        assert!(upload_resp.status().is_success());
+
+        let delete_req = test::TestRequest::delete().uri(&media_uri).to_request();
+        let delete_resp = test::call_service(&app, delete_req).await;
+        assert!(delete_resp.status().is_success());
+
+        // The upload is still in the history, which keeps its blob alive.
+        let history_uri = format!("/api/groups/{}/repos/{}/history", group.id(), repo.id());
+        let history_req = test::TestRequest::get().uri(&history_uri).to_request();
+        let history: serde_json::Value = test::call_and_read_body_json(&app, history_req).await;
+        let versions = history["versions"].as_array().unwrap();
+        assert_eq!(versions.len(), 2);
+        let uploaded_at = versions[1]["collection_hash"].as_str().unwrap().to_string();
+
+        let report_req = test::TestRequest::get().uri("/api/storage").to_request();
+        let before: serde_json::Value = test::call_and_read_body_json(&app, report_req).await;
+        assert_eq!(
+            before["complete"], true,
+            "Report should be complete: {before}"
+        );
+
+        let gc_req = test::TestRequest::post()
+            .uri("/api/storage/gc")
+            .to_request();
+        let gc: serde_json::Value = test::call_and_read_body_json(&app, gc_req).await;
+        assert!(
+            gc["freed_bytes"].as_u64().unwrap() < payload.len() as u64,
+            "Deleted file should be kept for the history: {gc}"
+        );
+
+        let report_req = test::TestRequest::get().uri("/api/storage").to_request();
+        let after: serde_json::Value = test::call_and_read_body_json(&app, report_req).await;
+        assert!(after["total_bytes"].as_u64().unwrap() >= payload.len() as u64);
+        assert_eq!(after["orphaned"]["blobs"], 0);
+
+        // Restoring needs no peer, since the blob is still stored.
+        let restore_req = test::TestRequest::post()
+            .uri(&format!("{history_uri}/restore"))
+            .set_json(json!({ "at": uploaded_at, "file_name": "deleted.bin" }))
+            .to_request();
+        let restore_resp = test::call_service(&app, restore_req).await;
+        assert!(restore_resp.status().is_success());
+        let download_req = test::TestRequest::get().uri(&media_uri).to_request();
+        let restored = test::call_and_read_body(&app, download_req).await;
+        assert_eq!(restored.to_vec(), payload);
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_evict_keeps_own_files() -> Result<()> {
+        let _path = init_test_backend("test_evict_keeps_own_files").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        repo.upload("mine.txt", b"my content".to_vec()).await?;
+
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .service(groups::scope())
+                    .service(storage::scope()),
+            ),
+        )
+        .await;
+
+        // Even a zero budget never evicts files from our own repos.
+        let evict_req = test::TestRequest::post()
+            .uri("/api/storage/evict")
+            .set_json(json!({ "policy": "byte_budget", "max_bytes": 0 }))
+            .to_request();
+        let evicted: serde_json::Value = test::call_and_read_body_json(&app, evict_req).await;
+        assert!(evicted["evicted"].as_array().unwrap().is_empty());
+        assert_eq!(evicted["freed_bytes"], 0);
+
+        let get_req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/mine.txt",
+                group.id(),
+                repo.id()
+            ))
+            .to_request();
+        let got = test::call_and_read_body(&app, get_req).await;
+        assert_eq!(got.to_vec(), b"my content".to_vec());
+
+        let bad_req = test::TestRequest::post()
+            .uri("/api/storage/evict")
+            .set_json(
+                json!({ "policy": "older_than", "secs": 0, "repo_id": repo.id().to_string() }),
+            )
+            .to_request();
+        let bad_resp = test::call_service(&app, bad_req).await;
+        assert_eq!(bad_resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_evict_other_members_file() -> Result<()> {
+        // Initialize main backend (joiner)
+        let _path = init_test_backend("test_evict_other_members_file_main").await?;
+
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .service(groups::scope())
+                    .service(storage::scope()),
+            ),
+        )
+        .await;
+
+        // Initialize secondary backend (creator) with unique namespace
+        let (path2, namespace2) = get_test_config("test_evict_other_members_file_secondary").await;
+        let store2 = iroh_blobs::store::fs::Store::load(path2.to_path_buf().join("iroh2")).await?;
+        let (veilid_api2, update_rx2) = init_veilid_for_tests(
+            path2.to_path_buf().as_path(),
+            namespace2,
+            Duration::from_secs(180),
+        )
+        .await?;
+        let backend2 =
+            Backend::from_dependencies(&path2.to_path_buf(), veilid_api2, update_rx2, store2)
+                .await
+                .unwrap();
+
+        wait_for_public_internet_ready(&backend2).await?;
+        {
+            use server::get_backend;
+            let backend = get_backend().await?;
+            wait_for_public_internet_ready(&backend).await?;
+        }
+
+        let group = backend2.create_group().await?;
+        group.set_name(TEST_GROUP_NAME).await?;
+        let repo = group.create_repo().await?;
+        repo.set_name(TEST_GROUP_NAME).await?;
+        repo.upload("creator.txt", b"creator content".to_vec())
+            .await?;
+
+        let join_req = test::TestRequest::post()
+            .uri("/api/groups/join_from_url")
+            .set_json(RequestUrl {
+                url: group.get_url()?,
+            })
+            .to_request();
+        let join_resp = test::call_service(&app, join_req).await;
+        assert!(join_resp.status().is_success());
+        let joined_group: SnowbirdGroup = test::read_body_json(join_resp).await;
+
+        // Wait until the creator's repo shows up on the joiner as read-only.
+        let mut retries = 20;
+        let read_only_repo = loop {
+            let refresh_req = test::TestRequest::post()
+                .uri(&format!("/api/groups/{}/refresh", joined_group.key))
+                .to_request();
+            let refresh_resp = test::call_service(&app, refresh_req).await;
+
+            if refresh_resp.status().is_success() {
+                let req = test::TestRequest::get()
+                    .uri(&format!("/api/groups/{}/repos", joined_group.key))
+                    .to_request();
+                let resp: ReposResponse = test::call_and_read_body_json(&app, req).await;
+                if let Some(repo) = resp.repos.into_iter().find(|r| !r.can_write) {
+                    break repo;
+                }
+            }
+
+            retries -= 1;
+            if retries == 0 {
+                panic!("Creator repo did not appear on the joiner after 20 refresh attempts.");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        };
+
+        let media_uri = format!(
+            "/api/groups/{}/repos/{}/media",
+            joined_group.key, read_only_repo.key
+        );
+        let is_downloaded = |files: &FilesResponse| {
+            files
+                .files
+                .iter()
+                .find(|file| file.name == "creator.txt")
+                .map(|file| file.is_downloaded)
+        };
+
+        // Downloading stores the creator's blob locally.
+        let mut retries = 10;
+        loop {
+            let get_req = test::TestRequest::get()
+                .uri(&format!("{media_uri}/creator.txt"))
+                .to_request();
+            let get_resp = test::call_service(&app, get_req).await;
+            if get_resp.status().is_success() {
+                let body = test::read_body(get_resp).await;
+                assert_eq!(body.to_vec(), b"creator content".to_vec());
+                break;
+            }
+            retries -= 1;
+            if retries == 0 {
+                panic!("Creator file could not be downloaded after 10 attempts.");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+        let list_req = test::TestRequest::get().uri(&media_uri).to_request();
+        let files: FilesResponse = test::call_and_read_body_json(&app, list_req).await;
+        assert_eq!(is_downloaded(&files), Some(true));
+
+        let evict_req = test::TestRequest::post()
+            .uri("/api/storage/evict")
+            .set_json(json!({
+                "policy": "older_than",
+                "secs": 0,
+                "group_id": joined_group.key,
+            }))
+            .to_request();
+        let evicted: serde_json::Value = test::call_and_read_body_json(&app, evict_req).await;
+        let evicted_files = evicted["evicted"].as_array().unwrap();
+        assert_eq!(evicted_files.len(), 1, "Only the creator's file: {evicted}");
+        assert_eq!(evicted_files[0]["file_name"], "creator.txt");
+        assert!(evicted["freed_bytes"].as_u64().unwrap() > 0);
+
+        // Still listed, but no longer stored locally.
+        let list_req = test::TestRequest::get().uri(&media_uri).to_request();
+        let files: FilesResponse = test::call_and_read_body_json(&app, list_req).await;
+        assert_eq!(is_downloaded(&files), Some(false));
+
+        // A later download fetches it from the creator again.
+        let get_req = test::TestRequest::get()
+            .uri(&format!("{media_uri}/creator.txt"))
+            .to_request();
+        let got = test::call_and_read_body(&app, get_req).await;
+        assert_eq!(got.to_vec(), b"creator content".to_vec());
+        let list_req = test::TestRequest::get().uri(&media_uri).to_request();
+        let files: FilesResponse = test::call_and_read_body_json(&app, list_req).await;
+        assert_eq!(is_downloaded(&files), Some(true));
+
+        // Clean up both backends - secondary first, then main
+        backend2.stop().await?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
         cleanup_test_resources().await?;
 
         Ok(())
@@ -742,6 +2260,107 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    #[serial]
+    async fn test_write_to_read_only_repo_forbidden() -> Result<()> {
+        // Initialize main backend (joiner)
+        let _path = init_test_backend("test_read_only_repo_main").await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        // Initialize secondary backend (creator) with unique namespace
+        let (path2, namespace2) = get_test_config("test_read_only_repo_secondary").await;
+        let store2 = iroh_blobs::store::fs::Store::load(path2.to_path_buf().join("iroh2")).await?;
+        let (veilid_api2, update_rx2) = init_veilid_for_tests(
+            path2.to_path_buf().as_path(),
+            namespace2,
+            Duration::from_secs(180),
+        )
+        .await?;
+        let backend2 =
+            Backend::from_dependencies(&path2.to_path_buf(), veilid_api2, update_rx2, store2)
+                .await
+                .unwrap();
+
+        wait_for_public_internet_ready(&backend2).await?;
+        {
+            use server::get_backend;
+            let backend = get_backend().await?;
+            wait_for_public_internet_ready(&backend).await?;
+        }
+
+        let group = backend2.create_group().await?;
+        group.set_name(TEST_GROUP_NAME).await?;
+        let repo = group.create_repo().await?;
+        repo.set_name(TEST_GROUP_NAME).await?;
+        repo.upload("creator.txt", b"creator content".to_vec())
+            .await?;
+
+        let join_req = test::TestRequest::post()
+            .uri("/api/groups/join_from_url")
+            .set_json(RequestUrl {
+                url: group.get_url()?,
+            })
+            .to_request();
+        let join_resp = test::call_service(&app, join_req).await;
+        assert!(join_resp.status().is_success());
+        let joined_group: SnowbirdGroup = test::read_body_json(join_resp).await;
+
+        // Wait until the creator's repo shows up on the joiner as read-only.
+        let mut retries = 20;
+        let read_only_repo = loop {
+            let refresh_req = test::TestRequest::post()
+                .uri(&format!("/api/groups/{}/refresh", joined_group.key))
+                .to_request();
+            let refresh_resp = test::call_service(&app, refresh_req).await;
+
+            if refresh_resp.status().is_success() {
+                let req = test::TestRequest::get()
+                    .uri(&format!("/api/groups/{}/repos", joined_group.key))
+                    .to_request();
+                let resp: ReposResponse = test::call_and_read_body_json(&app, req).await;
+                if let Some(repo) = resp.repos.into_iter().find(|r| !r.can_write) {
+                    break repo;
+                }
+            }
+
+            retries -= 1;
+            if retries == 0 {
+                panic!("Creator repo did not appear on the joiner after 20 refresh attempts.");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        };
+
+        let media_uri = format!(
+            "/api/groups/{}/repos/{}/media/creator.txt",
+            joined_group.key, read_only_repo.key
+        );
+
+        let upload_req = test::TestRequest::post()
+            .uri(&media_uri)
+            .set_payload(b"overwrite attempt".to_vec())
+            .to_request();
+        let upload_resp = test::call_service(&app, upload_req).await;
+        assert_eq!(upload_resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(upload_resp).await;
+        assert_eq!(body["code"], "not_writable");
+
+        let delete_req = test::TestRequest::delete().uri(&media_uri).to_request();
+        let delete_resp = test::call_service(&app, delete_req).await;
+        assert_eq!(delete_resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(delete_resp).await;
+        assert_eq!(body["code"], "not_writable");
+
+        // Clean up both backends - secondary first, then main
+        backend2.stop().await?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
     // P2P tests: use in-test retries (like save-dweb-backend) so nextest retries + internal
     // retry loops give the Veilid network time to converge.
     #[actix_web::test]
@@ -1093,6 +2712,90 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    #[serial]
+    async fn test_refresh_group_concurrently() -> Result<()> {
+        let _path = init_test_backend("test_refresh_concurrently").await?;
+
+        let (group, repo_ids) = {
+            use server::get_backend;
+            let backend = get_backend().await?;
+            let mut group = backend.create_group().await?;
+            group.set_name(TEST_GROUP_NAME).await?;
+            let mut repo_ids = Vec::new();
+            for index in 0..3 {
+                let repo = group.create_repo().await?;
+                repo.set_name(&format!("Repo {index}")).await?;
+                repo.upload("file.txt", format!("content {index}").into_bytes())
+                    .await?;
+                repo_ids.push(repo.id().to_string());
+            }
+            (group, repo_ids)
+        };
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+        let refresh = || {
+            test::TestRequest::post()
+                .uri(&format!("/api/groups/{}/refresh", group.id()))
+                .to_request()
+        };
+
+        // Fewer slots than repos, so some repos wait for a slot to free up.
+        server::set_config(Some(config::ServerConfig {
+            refresh_concurrency: 2,
+            ..config::ServerConfig::default()
+        }));
+        let refresh_data: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+        assert_eq!(refresh_data["complete"], true);
+        let repos = refresh_data["repos"].as_array().unwrap();
+        let mut refreshed_ids: Vec<String> = repos
+            .iter()
+            .map(|repo| repo["repo_id"].as_str().unwrap().to_string())
+            .collect();
+        refreshed_ids.sort();
+        let mut expected_ids = repo_ids.clone();
+        expected_ids.sort();
+        assert_eq!(refreshed_ids, expected_ids, "Every repo is refreshed once");
+        for repo in repos {
+            assert_eq!(repo["timed_out"], false, "No repo should time out: {repo}");
+            assert_eq!(repo["all_files"][0], "file.txt");
+        }
+
+        // A deadline that has already passed leaves the repos unfinished.
+        server::set_config(Some(config::ServerConfig {
+            refresh_deadline: Duration::ZERO,
+            ..config::ServerConfig::default()
+        }));
+        let refresh_data: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+        assert_eq!(refresh_data["complete"], false);
+        let repos = refresh_data["repos"].as_array().unwrap();
+        assert_eq!(
+            repos.len(),
+            repo_ids.len(),
+            "Timed out repos are still listed"
+        );
+        let timed_out: Vec<_> = repos
+            .iter()
+            .filter(|repo| repo["timed_out"] == true)
+            .collect();
+        assert!(!timed_out.is_empty(), "Repos should be marked as timed out");
+        for repo in timed_out {
+            let index = repo_ids
+                .iter()
+                .position(|id| id == repo["repo_id"].as_str().unwrap())
+                .expect("Timed out repo should be one of the group's");
+            assert_eq!(repo["name"], format!("Repo {index}"));
+            assert!(repo["all_files"].as_array().unwrap().is_empty());
+            assert!(repo["error"].is_string());
+        }
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
     #[actix_web::test]
     #[serial]
     async fn test_refresh_group_with_file() -> Result<()> {
@@ -1104,15 +2807,7 @@ mod tests {
         let _path = init_test_backend("test_refresh_with_file").await?;
 
         // Create a group with a repo and upload a file
-        let (group, repo) = {
-            use server::get_backend;
-            let backend = get_backend().await?;
-            let mut group = backend.create_group().await?;
-            group.set_name(TEST_GROUP_NAME).await?;
-            let repo = group.create_repo().await?;
-            repo.set_name("Test Repo").await?;
-            (group, repo)
-        };
+        let (group, repo) = create_group_with_repo().await?;
 
         // Upload a file
         let file_name = "test.txt";
@@ -1183,6 +2878,104 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    #[serial]
+    async fn test_refresh_reports_changes_since_last_sync() -> Result<()> {
+        let _path = init_test_backend("test_refresh_reports_changes").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        repo.upload("first.txt", b"first".to_vec()).await?;
+
+        let app = test::init_service(
+            App::new()
+                .service(status)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+
+        let refresh = || {
+            test::TestRequest::post()
+                .uri(&format!("/api/groups/{}/refresh", group.id()))
+                .to_request()
+        };
+
+        // The first refresh reports every file as new.
+        let first: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+        let changes = &first["repos"][0]["changes"];
+        assert!(changes["previous_hash"].is_null());
+        assert_eq!(changes["added"], serde_json::json!(["first.txt"]));
+        let first_hash = changes["current_hash"].clone();
+        assert!(first_hash.is_string(), "current_hash should be set");
+
+        repo.upload("second.txt", b"second".to_vec()).await?;
+
+        let second: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+        let changes = &second["repos"][0]["changes"];
+        assert_eq!(changes["previous_hash"], first_hash);
+        assert_ne!(changes["current_hash"], first_hash);
+        assert_eq!(changes["added"], serde_json::json!(["second.txt"]));
+        assert!(changes["removed"].as_array().unwrap().is_empty());
+        assert!(changes["changed"].as_array().unwrap().is_empty());
+
+        // Nothing changed since the previous refresh.
+        let third: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+        let changes = &third["repos"][0]["changes"];
+        assert_eq!(changes["previous_hash"], changes["current_hash"]);
+        assert!(changes["added"].as_array().unwrap().is_empty());
+        assert_eq!(third["repos"][0]["all_files"].as_array().unwrap().len(), 2);
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_list_group_members() -> Result<()> {
+        let _path = init_test_backend("test_list_group_members").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+        repo.upload("first.txt", b"first".to_vec()).await?;
+
+        let app =
+            test::init_service(App::new().service(web::scope("/api").service(groups::scope())))
+                .await;
+
+        let members = || {
+            test::TestRequest::get()
+                .uri(&format!("/api/groups/{}/members", group.id()))
+                .to_request()
+        };
+
+        let before: serde_json::Value = test::call_and_read_body_json(&app, members()).await;
+        let member = &before["members"][0];
+        assert_eq!(before["members"].as_array().unwrap().len(), 1);
+        assert_eq!(member["repo_id"], repo.id().to_string());
+        assert_eq!(member["name"], "Test Repo");
+        assert_eq!(member["is_self"], true);
+        assert_eq!(member["file_count"], 1);
+        assert_eq!(member["local_file_count"], 1);
+        // No refresh has seen the collection yet.
+        assert!(member["changed_at"].is_null());
+
+        let refresh_req = test::TestRequest::post()
+            .uri(&format!("/api/groups/{}/refresh", group.id()))
+            .to_request();
+        let refresh: serde_json::Value = test::call_and_read_body_json(&app, refresh_req).await;
+
+        let after: serde_json::Value = test::call_and_read_body_json(&app, members()).await;
+        let member = &after["members"][0];
+        assert_eq!(
+            member["collection_hash"],
+            refresh["repos"][0]["changes"]["current_hash"]
+        );
+        assert!(member["changed_at"].is_u64());
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
     #[actix_web::test]
     #[serial]
     async fn test_refresh_group_isolation_across_groups() -> Result<()> {
@@ -2109,6 +3902,289 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    #[serial]
+    async fn test_stop_start_stop() -> Result<()> {
+        clear_backend()?;
+
+        // The second round checks that a stopped server can be started and stopped again.
+        for round in 0..2 {
+            let path = TmpDir::new(&format!("test_stop_start_stop_{round}")).await?;
+            let base_dir = path.to_path_buf().to_string_lossy().into_owned();
+            let port = std::net::TcpListener::bind("127.0.0.1:0")?
+                .local_addr()?
+                .port();
+            let config = config::ServerConfig {
+                port,
+                enable_socket: false,
+                api_token: Some("test-token".to_string()),
+                ..config::ServerConfig::default()
+            };
+            let server = actix_web::rt::spawn(async move {
+                server::start_with_config(&base_dir, "", config).await
+            });
+
+            let events = tokio::task::spawn_blocking(move || open_event_stream(port, "test-token"))
+                .await??;
+
+            server::request_shutdown();
+            // An open event stream must end rather than hold up the drain.
+            tokio::task::spawn_blocking(move || wait_for_stream_end(events)).await??;
+
+            let result = tokio::time::timeout(Duration::from_secs(60), server)
+                .await
+                .unwrap_or_else(|_| panic!("server did not stop in round {round}"))?;
+            result?;
+            assert!(
+                !server::pin_sync_running(),
+                "Pin sync should stop with the server in round {round}"
+            );
+        }
+
+        clear_backend()?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_event_stream() -> Result<()> {
+        let _path = init_test_backend("test_event_stream").await?;
+
+        let (group, repo) = create_group_with_repo().await?;
+
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .service(groups::scope())
+                    .service(events::event_stream),
+            ),
+        )
+        .await;
+
+        let events_req = test::TestRequest::get().uri("/api/events").to_request();
+        let events_resp = test::call_service(&app, events_req).await;
+        assert!(events_resp.status().is_success());
+        assert_eq!(
+            events_resp.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+        let body = events_resp.into_body();
+        let mut body = std::pin::pin!(body);
+
+        // The first event always reports readiness.
+        let (name, data) = next_sse_event(&mut body).await.expect("stream ended early");
+        assert_eq!(name, "backend_ready");
+        assert_eq!(data["type"], "backend_ready");
+        assert!(data["ready"].is_boolean());
+
+        let upload_req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/groups/{}/repos/{}/media/events.txt",
+                group.id(),
+                repo.id()
+            ))
+            .set_payload("event payload")
+            .to_request();
+        let upload_resp = test::call_service(&app, upload_req).await;
+        assert!(upload_resp.status().is_success());
+
+        // Progress events may come first; the last one for the upload is marked done.
+        let done = loop {
+            let (name, data) = next_sse_event(&mut body).await.expect("stream ended early");
+            if name == "upload_progress" && data["done"] == true {
+                break data;
+            }
+        };
+        assert_eq!(done["repo_id"], repo.id().to_string());
+        assert_eq!(done["file_name"], "events.txt");
+        assert_eq!(done["bytes_received"], 13);
+
+        // Open streams end when the server shuts down.
+        events::close_streams();
+        let ended = next_sse_event(&mut body).await;
+        events::reopen_streams();
+        assert!(ended.is_none(), "Stream should end on shutdown: {ended:?}");
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_server_config_from_env() -> Result<()> {
+        let vars = [
+            ("SAVE_BIND_ADDRESS", "0.0.0.0"),
+            ("SAVE_PORT", "9090"),
+            ("SAVE_ENABLE_TCP", "off"),
+            ("SAVE_ENABLE_SOCKET", "maybe"),
+            ("SAVE_MAX_UPLOAD_BYTES", "0"),
+            ("SAVE_REFRESH_CONCURRENCY", "8"),
+            ("SAVE_REFRESH_DEADLINE_SECS", "soon"),
+        ];
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+        let config = config::ServerConfig::from_env();
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+
+        assert_eq!(config.bind_address, "0.0.0.0".parse::<std::net::IpAddr>()?);
+        assert_eq!(config.port, 9090);
+        assert!(!config.enable_tcp);
+        assert_eq!(config.refresh_concurrency, 8);
+        // Zero and unparsable values fall back to the defaults.
+        let defaults = config::ServerConfig::default();
+        assert!(config.enable_socket);
+        assert_eq!(config.max_upload_bytes, defaults.max_upload_bytes);
+        assert_eq!(config.refresh_deadline, defaults.refresh_deadline);
+        assert!(config.validate().is_ok());
+
+        let no_listeners = config::ServerConfig {
+            enable_tcp: false,
+            enable_socket: false,
+            ..config::ServerConfig::default()
+        };
+        assert!(no_listeners.validate().is_err());
+        let no_upload_room = config::ServerConfig {
+            max_upload_bytes: 0,
+            ..config::ServerConfig::default()
+        };
+        assert!(no_upload_room.validate().is_err());
+        let no_refresh_slots = config::ServerConfig {
+            refresh_concurrency: 0,
+            ..config::ServerConfig::default()
+        };
+        assert!(no_refresh_slots.validate().is_err());
+
+        assert_eq!(config::parse_flag(" Yes "), Some(true));
+        assert_eq!(config::parse_flag("0"), Some(false));
+        assert_eq!(config::parse_flag("maybe"), None);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_blank_api_token_is_replaced() -> Result<()> {
+        env::set_var("SAVE_API_TOKEN", "  ");
+        let from_env = config::ServerConfig::from_env();
+        env::remove_var("SAVE_API_TOKEN");
+
+        // The blank env token is replaced, like one passed through a bridge or flag.
+        for token in [None, Some(String::new()), from_env.api_token.clone()] {
+            let mut config = config::ServerConfig {
+                api_token: token,
+                ..from_env.clone()
+            };
+            let generated = server::ensure_api_token(&mut config);
+            assert!(!generated.trim().is_empty(), "A token should be generated");
+            assert_eq!(config.api_token.as_deref(), Some(generated.as_str()));
+            assert_eq!(server::api_token(), Some(generated));
+        }
+
+        // A real token is kept, and never ends up in logs.
+        let mut config = config::ServerConfig {
+            api_token: Some("secret-token".to_string()),
+            ..from_env
+        };
+        assert_eq!(server::ensure_api_token(&mut config), "secret-token");
+        assert!(!format!("{config:?}").contains("secret-token"));
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_api_requires_bearer_token() -> Result<()> {
+        let _path = init_test_backend("test_api_requires_bearer_token").await?;
+
+        let app = test::init_service(
+            App::new().service(health).service(
+                web::scope("/api")
+                    .wrap(actix_bearer_auth::BearerAuth::new("test-token"))
+                    .service(groups::scope()),
+            ),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success(), "Health should not need a token");
+
+        let req = test::TestRequest::get().uri("/api/groups").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/api/groups")
+            .insert_header(("Authorization", "Bearer wrong-token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/api/groups")
+            .insert_header(("Authorization", "Bearer test-token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success(), "Valid token should be accepted");
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_structured_error_codes() -> Result<()> {
+        let _path = init_test_backend("test_structured_error_codes").await?;
+
+        let app = test::init_service(
+            App::new()
+                .wrap(actix_legacy_errors::LegacyErrors)
+                .service(web::scope("/api").service(groups::scope())),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/groups/not-a-valid-key!")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["code"], "invalid_key");
+
+        let fake_group_id = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode([0u8; 32]);
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{fake_group_id}"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "group_not_found");
+
+        // The compatibility header restores the plain string body.
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/groups/{fake_group_id}"))
+            .insert_header((error::LEGACY_ERRORS_HEADER, "1"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+        let body: String = test::read_body_json(resp).await;
+        assert!(
+            body.starts_with("Something went wrong: "),
+            "Legacy body should be a plain string, got {body}"
+        );
+
+        cleanup_test_resources().await?;
+
+        Ok(())
+    }
+
     #[actix_web::test]
     #[serial]
     async fn test_idempotent_create_repo_after_join() -> Result<()> {
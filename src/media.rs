@@ -1,76 +1,393 @@
 use crate::constants::TAG;
-use crate::error::{AppError, AppResult};
-use crate::log_info;
-use crate::models::{GroupRepoMediaPath, GroupRepoPath};
+use crate::error::{ApiError, AppError, AppResult};
+use crate::events::{self, ServerEvent};
+use crate::history::{self, Change};
+use crate::jobs;
+use crate::metadata;
+use crate::models::{GroupRepoMediaPath, GroupRepoPath, SnowbirdFile};
 use crate::server::get_backend;
-use crate::utils::create_veilid_cryptokey_from_base64;
+use crate::storage;
+use crate::utils::{
+    create_veilid_cryptokey_from_base64, download_from_peers, ensure_writable,
+    fetch_file_from_peers, find_file_hash, find_group, find_repo, open_decrypted_file,
+    validate_file_name,
+};
+use crate::{log_debug, log_error, log_info};
+use actix_multipart::Multipart;
 use actix_web::{
-    delete, error::BlockingError, get, http::header, post, web, HttpResponse, Responder, Scope,
+    body::SizedStream,
+    delete,
+    error::BlockingError,
+    get,
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType},
+    post, web, HttpRequest, HttpResponse, Responder, Scope,
 };
-use bytes::{Bytes, BytesMut};
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures::future;
+use futures::stream;
 use futures::Stream;
 use futures::StreamExt;
+use iroh_blobs::store::fs::Store as BlobStore;
+use iroh_blobs::Hash;
+use save_dweb_backend::repo::Repo;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::fmt;
 use std::io;
 use std::time::Duration;
+use tokio::sync::mpsc;
 
 const MEDIA_DOWNLOAD_OVERALL_TIMEOUT: Duration = Duration::from_secs(55);
 
+// Number of payload chunks buffered between the HTTP body and the blob store.
+const UPLOAD_CHANNEL_CAPACITY: usize = 8;
+
+// Upload progress events are published at most once per this many bytes.
+const UPLOAD_PROGRESS_INTERVAL: u64 = 1024 * 1024;
+
+// Largest page `list_files` returns when a `limit` is given.
+const MAX_LIST_LIMIT: usize = 1000;
+
 pub fn scope() -> Scope {
     web::scope("/media")
+        .service(upload_files)
         .service(upload_file)
         .service(list_files)
         .service(delete_file)
+        .service(rename_file)
+        .service(copy_file)
         .service(download_file)
+        .service(fetch_file)
 }
 
 pub fn from_blocking<T>(result: Result<T, BlockingError>) -> AppResult<T> {
     result.map_err(AppError::from)
 }
 
-async fn handle_file_stream(
-    mut file_data: impl Stream<Item = Result<Bytes, io::Error>> + Unpin,
-) -> AppResult<(usize, Bytes)> {
-    let mut buffer = BytesMut::new();
-    let mut length = 0;
+/// Inclusive byte range resolved against the plaintext length of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+/// Interpret a `Range` header against a file of `total` bytes.
+///
+/// Only a single `bytes=` range is honoured. Malformed or multi-range headers fall back to
+/// the full body, as RFC 9110 allows servers to ignore a Range they do not support.
+fn parse_range_header(value: Option<&str>, total: u64) -> RangeRequest {
+    let Some(spec) = value.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // Suffix range: the last `end` bytes.
+        let Ok(suffix) = end.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+        if suffix == 0 || total == 0 {
+            return RangeRequest::Unsatisfiable;
+        }
+        return RangeRequest::Partial(ByteRange {
+            start: total.saturating_sub(suffix),
+            end: total - 1,
+        });
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return RangeRequest::Full;
+    };
+    let end = if end.is_empty() {
+        total.saturating_sub(1)
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) => end.min(total.saturating_sub(1)),
+            Err(_) => return RangeRequest::Full,
+        }
+    };
+
+    if start >= total || end < start {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Partial(ByteRange { start, end })
+}
+
+/// Trim a plaintext stream down to `range`, stopping as soon as the range is complete. The
+/// backend cannot start decrypting mid-file, so the bytes before `range` are still read.
+fn byte_range_stream<S>(stream: S, range: ByteRange) -> impl Stream<Item = io::Result<Bytes>>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    let end_exclusive = range.end + 1;
+
+    stream
+        .scan(0u64, move |position, chunk| {
+            let item = match chunk {
+                Err(e) => Some(Some(Err(e))),
+                Ok(chunk) => {
+                    let chunk_start = *position;
+                    let chunk_end = chunk_start + chunk.len() as u64;
+                    *position = chunk_end;
+
+                    if chunk_start >= end_exclusive {
+                        None
+                    } else if chunk_end <= range.start {
+                        Some(None)
+                    } else {
+                        let from = range.start.saturating_sub(chunk_start) as usize;
+                        let to = (end_exclusive.min(chunk_end) - chunk_start) as usize;
+                        Some(Some(Ok(chunk.slice(from..to))))
+                    }
+                }
+            };
+            future::ready(item)
+        })
+        .filter_map(future::ready)
+}
+
+struct UploadedBody {
+    size: u64,
+    // Leading bytes of the body, kept for content sniffing.
+    head: Vec<u8>,
+}
+
+enum UploadAbort {
+    TooLarge,
+    Empty,
+    Payload(anyhow::Error),
+}
 
-    while let Some(chunk_result) = file_data.next().await {
-        let chunk = chunk_result.map_err(|e| AppError(anyhow::Error::new(e)))?;
-        buffer.extend_from_slice(&chunk);
-        length += chunk.len();
+impl UploadAbort {
+    fn into_error(self, limit: u64) -> AppError {
+        match self {
+            UploadAbort::TooLarge => ApiError::PayloadTooLarge(limit).into(),
+            UploadAbort::Empty => ApiError::BadRequest("File content is empty".to_string()).into(),
+            UploadAbort::Payload(e) => e.into(),
+        }
     }
+}
+
+/// Forward request body chunks to the blob store until the body ends or passes `limit`.
+///
+/// On failure an error item is pushed into the channel so the backend discards the
+/// partially written blob instead of committing a truncated file.
+async fn forward_payload<E: fmt::Display>(
+    body: impl Stream<Item = Result<Bytes, E>>,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    limit: u64,
+    on_progress: impl Fn(u64),
+) -> Result<UploadedBody, UploadAbort> {
+    let mut body = std::pin::pin!(body);
+    let mut received: u64 = 0;
+    let mut reported: u64 = 0;
+    let mut head: Vec<u8> = Vec::new();
+    let mut forwarding = true;
 
-    let final_buffer = web::block(move || buffer.freeze()).await?;
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                let _ = tx
+                    .send(Err(io::Error::other(format!(
+                        "Failed to read file chunk: {e}"
+                    ))))
+                    .await;
+                return Err(UploadAbort::Payload(anyhow!(
+                    "Failed to read file chunk: {e}"
+                )));
+            }
+        };
+
+        received += chunk.len() as u64;
+        if received > limit {
+            let _ = tx
+                .send(Err(io::Error::other("Upload exceeds size limit")))
+                .await;
+            return Err(UploadAbort::TooLarge);
+        }
+
+        if !forwarding {
+            continue;
+        }
 
-    Ok((length, final_buffer))
+        if received - reported >= UPLOAD_PROGRESS_INTERVAL {
+            reported = received;
+            on_progress(received);
+        }
+
+        if head.len() < metadata::SNIFF_LENGTH {
+            let wanted = (metadata::SNIFF_LENGTH - head.len()).min(chunk.len());
+            head.extend_from_slice(&chunk[..wanted]);
+        }
+
+        if tx.send(Ok(chunk)).await.is_err() {
+            // The backend stopped reading; its own error is reported by the caller. The
+            // rest of the body is still read so it counts toward `limit`.
+            forwarding = false;
+        }
+    }
+
+    if received == 0 {
+        let _ = tx
+            .send(Err(io::Error::other("File content is empty")))
+            .await;
+        return Err(UploadAbort::Empty);
+    }
+
+    Ok(UploadedBody {
+        size: received,
+        head,
+    })
+}
+
+/// Read and drop the rest of a body, returning its size, so parts that are not stored
+/// still count toward the upload limit.
+async fn discard_payload<E: fmt::Display>(
+    body: impl Stream<Item = Result<Bytes, E>>,
+    limit: u64,
+) -> Result<u64, UploadAbort> {
+    let mut body = std::pin::pin!(body);
+    let mut received: u64 = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk =
+            chunk.map_err(|e| UploadAbort::Payload(anyhow!("Failed to read file chunk: {e}")))?;
+        received += chunk.len() as u64;
+        if received > limit {
+            return Err(UploadAbort::TooLarge);
+        }
+    }
+    Ok(received)
+}
+
+fn inline_disposition(file_name: &str) -> ContentDisposition {
+    ContentDisposition {
+        disposition: DispositionType::Inline,
+        parameters: vec![DispositionParam::Filename(file_name.to_string())],
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FileSort {
+    Name,
+    Size,
+    Date,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListFilesQuery {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    prefix: Option<String>,
+    sort: Option<FileSort>,
+    #[serde(default)]
+    order: SortOrder,
+    downloaded: Option<bool>,
+    /// Collection hash of an earlier version to list instead of the current files.
+    at: Option<String>,
+}
+
+/// A listed file before its download state has been looked up.
+struct FileEntry {
+    name: String,
+    hash: Hash,
+    metadata: metadata::FileMetadata,
+    is_downloaded: Option<bool>,
+}
+
+/// Mark which of `entries` are stored completely, checking the blob store directly rather
+/// than asking the backend per file.
+async fn lookup_files(store: &BlobStore, entries: &mut [FileEntry]) {
+    for entry in entries {
+        if entry.is_downloaded.is_none() {
+            entry.is_downloaded =
+                Some(storage::local_blob_size(store, &entry.hash).await.is_some());
+        }
+    }
+}
+
+fn sort_entries(entries: &mut [FileEntry], sort: FileSort, order: SortOrder) {
+    match sort {
+        FileSort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        FileSort::Size => {
+            entries.sort_by(|a, b| (a.metadata.size, &a.name).cmp(&(b.metadata.size, &b.name)))
+        }
+        FileSort::Date => entries.sort_by(|a, b| {
+            (a.metadata.uploaded_at, &a.name).cmp(&(b.metadata.uploaded_at, &b.name))
+        }),
+    }
+    if let SortOrder::Desc = order {
+        entries.reverse();
+    }
 }
 
 #[get("")]
-async fn list_files(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
+async fn list_files(
+    path: web::Path<GroupRepoPath>,
+    query: web::Query<ListFilesQuery>,
+) -> AppResult<impl Responder> {
     let path_params = path.into_inner();
     let group_id = &path_params.group_id;
     let repo_id = &path_params.repo_id;
+    let query = query.into_inner();
+
+    // The cursor is the offset of the next page, opaque to clients.
+    let offset = match query.cursor.as_deref() {
+        None | Some("") => 0,
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| ApiError::BadRequest(format!("invalid cursor: {cursor}")))?,
+    };
+    let limit = query.limit.map(|limit| limit.clamp(1, MAX_LIST_LIMIT));
 
     // Fetch the backend and group
-    let crypto_key = create_veilid_cryptokey_from_base64(group_id)?;
     let backend = get_backend().await?;
-    let group = backend.get_group(&crypto_key).await?;
+    let group = find_group(&backend, group_id).await?;
 
     // Fetch the repo
-    let repo_crypto_key = create_veilid_cryptokey_from_base64(repo_id)?;
-    let repo = group.get_repo(&repo_crypto_key).await?;
+    let repo = find_repo(&group, repo_id).await?;
+    let repo_key = repo.id().to_string();
 
-    if !repo.can_write() {
-        match repo.get_hash_from_dht().await {
-            Ok(hash) => {
-                if !group.has_hash(&hash).await? {
-                    group
-                        .download_hash_from_peers_with_timeout(
-                            &hash,
-                            Some(MEDIA_DOWNLOAD_OVERALL_TIMEOUT),
-                        )
-                        .await?;
-                }
+    // Names and hashes come from a single load of the collection, the current one or an
+    // earlier version, rather than a backend lookup per file.
+    let collection_hash = match query.at.as_deref() {
+        Some(at) => Some(history::find_version(&repo_key, at)?),
+        None => match repo.get_hash_from_dht().await {
+            Ok(hash) => Some(hash),
+            // A repo we own that has no files has never published a collection.
+            Err(_) if repo.can_write() && repo.list_files().await?.is_empty() => None,
+            Err(err) if repo.can_write() => {
+                return Err(anyhow!("Error getting repo hash from DHT: {err}").into())
             }
             Err(err) => {
                 log_info!(
@@ -79,89 +396,209 @@ async fn list_files(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder>
                     repo_id,
                     err
                 );
-                return Ok(HttpResponse::Ok().json(json!({ "files": [] })));
+                return Ok(HttpResponse::Ok().json(json!({
+                    "files": [],
+                    "total": 0,
+                    "next_cursor": null
+                })));
             }
-        }
-    }
+        },
+    };
+    let files = match collection_hash {
+        Some(collection_hash) => history::snapshot(&group, &collection_hash).await?,
+        None => Vec::new(),
+    };
 
-    // List files and check if they are downloaded
-    let files = repo.list_files().await?;
-    let mut files_with_status = Vec::new();
+    // Filter and sort on names and local metadata first, so store lookups are only needed
+    // for the requested page (or for every match when filtering on download state).
+    let mut entries: Vec<FileEntry> = files
+        .into_iter()
+        .filter(|(name, _)| {
+            query
+                .prefix
+                .as_deref()
+                .is_none_or(|prefix| name.starts_with(prefix))
+        })
+        .map(|(name, hash)| FileEntry {
+            metadata: metadata::get_current(&repo_key, &name, &hash),
+            name,
+            hash,
+            is_downloaded: None,
+        })
+        .collect();
 
-    for file_name in files {
-        let file_hash = match repo.get_file_hash(&file_name).await {
-            Ok(hash) => hash,
-            Err(_) => continue, // Handle the error or skip the file if there's an issue
-        };
-        let is_downloaded = group.has_hash(&file_hash).await.unwrap_or(false); // Check if the file is local
-        files_with_status.push(json!({
-            "name": file_name,
-            "hash": file_hash,
-            "is_downloaded": is_downloaded
-        }));
+    if let Some(sort) = query.sort {
+        sort_entries(&mut entries, sort, query.order);
+    }
+
+    let store = storage::blob_store(&backend).await?;
+    if let Some(downloaded) = query.downloaded {
+        lookup_files(&store, &mut entries).await;
+        entries.retain(|entry| entry.is_downloaded == Some(downloaded));
     }
-    Ok(HttpResponse::Ok().json(json!({ "files": files_with_status })))
+
+    let total = entries.len();
+    let end = limit.map_or(total, |limit| offset.saturating_add(limit).min(total));
+    let mut page: Vec<FileEntry> = entries
+        .into_iter()
+        .skip(offset)
+        .take(end.saturating_sub(offset))
+        .collect();
+    let next_cursor = (end < total).then(|| end.to_string());
+
+    lookup_files(&store, &mut page).await;
+    let files: Vec<SnowbirdFile> = page
+        .into_iter()
+        .map(|entry| SnowbirdFile {
+            name: entry.name,
+            hash: entry.hash,
+            is_downloaded: entry.is_downloaded.unwrap_or(false),
+            mime_type: entry.metadata.mime_type,
+            size: entry.metadata.size,
+            uploaded_at: entry.metadata.uploaded_at,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "files": files,
+        "total": total,
+        "next_cursor": next_cursor
+    })))
 }
 
 #[get("/{file_name}")]
-async fn download_file(path: web::Path<GroupRepoMediaPath>) -> AppResult<impl Responder> {
+async fn download_file(
+    req: HttpRequest,
+    path: web::Path<GroupRepoMediaPath>,
+) -> AppResult<impl Responder> {
     let path_params = path.into_inner();
     let group_id = &path_params.group_id;
     let repo_id = &path_params.repo_id;
     let file_name = &path_params.file_name;
 
     // Fetch the backend and group
-    let crypto_key = create_veilid_cryptokey_from_base64(group_id)?;
     let backend = get_backend().await?;
-    let group = backend.get_group(&crypto_key).await?;
+    let group = find_group(&backend, group_id).await?;
 
     // Fetch the repo
-    let repo_crypto_key = create_veilid_cryptokey_from_base64(repo_id)?;
-    let repo = group.get_repo(&repo_crypto_key).await?;
+    let repo = find_repo(&group, repo_id).await?;
 
     if !repo.can_write() {
         let collection_hash = repo.get_hash_from_dht().await?;
         if !group.has_hash(&collection_hash).await? {
-            group
-                .download_hash_from_peers_with_timeout(
-                    &collection_hash,
-                    Some(MEDIA_DOWNLOAD_OVERALL_TIMEOUT),
-                )
-                .await?;
+            download_from_peers(&group, &collection_hash, MEDIA_DOWNLOAD_OVERALL_TIMEOUT).await?;
         }
     }
 
     // Get the file hash
-    let file_hash = repo.get_file_hash(file_name).await?;
+    let file_hash = find_file_hash(&repo, file_name).await?;
 
+    // Held until the file is open so eviction cannot drop the blob after the check.
+    let reading = storage::blob_write_guard().await;
     if !group.has_hash(&file_hash).await? {
-        group
-            .download_hash_from_peers_with_timeout(&file_hash, Some(MEDIA_DOWNLOAD_OVERALL_TIMEOUT))
-            .await?;
-    }
-    // Trigger file download from peers using the hash
-    let file_data = repo.get_file_stream(file_name).await?;
-
-    let (encrypted_length, buffered_data) = handle_file_stream(file_data).await?;
-
-    // Decrypt the file data
-    let (decrypted_data, was_encrypted) = repo
-        .decrypt_file_data(&buffered_data)
-        .map_err(|e| AppError(anyhow::Error::msg(format!("Failed to decrypt file: {e}"))))?;
-
-    if was_encrypted {
-        log_info!(
-            TAG,
-            "File decrypted: {} bytes → {} bytes",
-            encrypted_length,
-            decrypted_data.len()
+        fetch_file_from_peers(
+            &group,
+            &repo.id().to_string(),
+            file_name,
+            &file_hash,
+            MEDIA_DOWNLOAD_OVERALL_TIMEOUT,
+        )
+        .await?;
+    }
+
+    // Decrypt chunk by chunk so peak memory stays bounded regardless of file size.
+    let (plaintext_length, file_data) = open_decrypted_file(&repo, file_name).await?;
+    drop(reading);
+
+    // Pull the first chunk up front so the content type can be sniffed, then put it back.
+    let mut file_data = Box::pin(file_data);
+    let first_chunk = match file_data.next().await {
+        Some(chunk) => Some(chunk.map_err(|e| AppError(anyhow::Error::new(e)))?),
+        None => None,
+    };
+
+    let repo_key = repo.id().to_string();
+    let mime_type = if metadata::has_mime_type(&repo_key, file_name, &file_hash) {
+        metadata::get_current(&repo_key, file_name, &file_hash)
+            .mime_type
+            .unwrap_or_default()
+    } else {
+        let sniffed = metadata::sniff_mime_type(file_name, first_chunk.as_deref());
+        metadata::record_download(
+            &repo_key,
+            file_name,
+            &file_hash,
+            Some(sniffed.clone()),
+            plaintext_length,
         );
+        sniffed
+    };
+    metadata::record_view(&repo_key, file_name, &file_hash);
+
+    let file_data = stream::iter(first_chunk.map(Ok)).chain(file_data);
+
+    let range_header = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+
+    match parse_range_header(range_header, plaintext_length) {
+        RangeRequest::Full => Ok(HttpResponse::Ok()
+            .content_type(mime_type)
+            .insert_header(inline_disposition(file_name))
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .body(SizedStream::new(plaintext_length, Box::pin(file_data)))),
+        RangeRequest::Partial(range) => {
+            log_debug!(
+                TAG,
+                "Serving bytes {}-{}/{} of {}",
+                range.start,
+                range.end,
+                plaintext_length,
+                file_name
+            );
+            Ok(HttpResponse::PartialContent()
+                .content_type(mime_type)
+                .insert_header(inline_disposition(file_name))
+                .insert_header((header::ACCEPT_RANGES, "bytes"))
+                .insert_header((
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end, plaintext_length),
+                ))
+                .body(SizedStream::new(
+                    range.len(),
+                    Box::pin(byte_range_stream(file_data, range)),
+                )))
+        }
+        RangeRequest::Unsatisfiable => Ok(HttpResponse::RangeNotSatisfiable()
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .insert_header((header::CONTENT_RANGE, format!("bytes */{plaintext_length}")))
+            .finish()),
     }
+}
 
-    Ok(HttpResponse::Ok()
-        .content_type("application/octet-stream")
-        .insert_header((header::CONTENT_LENGTH, decrypted_data.len()))
-        .body(decrypted_data))
+#[post("/{file_name}/fetch")]
+async fn fetch_file(path: web::Path<GroupRepoMediaPath>) -> AppResult<impl Responder> {
+    crate::server::ensure_backend_ready().await?;
+    let path_params = path.into_inner();
+
+    // Validate ids up front so a malformed key fails the request rather than the job.
+    create_veilid_cryptokey_from_base64(&path_params.group_id)?;
+    create_veilid_cryptokey_from_base64(&path_params.repo_id)?;
+
+    let job = jobs::enqueue_download(
+        &path_params.group_id,
+        &path_params.repo_id,
+        &path_params.file_name,
+    );
+    log_info!(
+        TAG,
+        "Queued download job {} for {}",
+        job.id,
+        path_params.file_name
+    );
+
+    Ok(HttpResponse::Accepted().json(job))
 }
 
 #[delete("/{file_name}")]
@@ -172,74 +609,275 @@ async fn delete_file(path: web::Path<GroupRepoMediaPath>) -> AppResult<impl Resp
     let file_name = &path_params.file_name;
 
     // Fetch the backend and group
-    let crypto_key = create_veilid_cryptokey_from_base64(group_id)?;
     let backend = get_backend().await?;
-    let group = backend.get_group(&crypto_key).await?;
+    let group = find_group(&backend, group_id).await?;
 
     // Fetch the repo
-    let repo_crypto_key = create_veilid_cryptokey_from_base64(repo_id)?;
-    let repo = group.get_repo(&repo_crypto_key).await?;
+    let repo = find_repo(&group, repo_id).await?;
+
+    ensure_writable(&repo)?;
+
+    // Report unknown names as file_not_found rather than a backend failure.
+    find_file_hash(&repo, file_name).await?;
 
     // Delete the file and update the collection
     let collection_hash = repo.delete_file(file_name).await?;
+    let repo_key = repo.id().to_string();
+    metadata::remove(&repo_key, file_name);
+    history::record(
+        &repo_key,
+        &collection_hash,
+        Change::Delete {
+            file_name: file_name.clone(),
+        },
+    );
 
     Ok(HttpResponse::Ok().json(collection_hash))
 }
 
+#[derive(Deserialize)]
+struct RenameFileRequest {
+    name: String,
+}
+
+/// Give a file a new name in the same repo. The blob is reused, and the old name is removed
+/// in the same collection update.
+#[post("/{file_name}/rename")]
+async fn rename_file(
+    path: web::Path<GroupRepoMediaPath>,
+    body: web::Json<RenameFileRequest>,
+) -> AppResult<impl Responder> {
+    let path_params = path.into_inner();
+    let file_name = &path_params.file_name;
+    let new_name = body.into_inner().name;
+    validate_file_name(&new_name)?;
+
+    // Fetch the backend and group
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+
+    // Fetch the repo
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+    ensure_writable(&repo)?;
+
+    let file_hash = find_file_hash(&repo, file_name).await?;
+    if repo.get_file_hash(&new_name).await.is_ok() {
+        return Err(ApiError::FileExists(new_name).into());
+    }
+
+    let _writing = storage::blob_write_guard().await;
+    let updated_collection_hash = repo
+        .update_collection(vec![(new_name.clone(), file_hash)], vec![file_name.clone()])
+        .await
+        .map_err(|e| anyhow!("Failed to rename file: {e}"))?;
+    let repo_key = repo.id().to_string();
+    metadata::rename(&repo_key, file_name, &new_name);
+    history::record(
+        &repo_key,
+        &updated_collection_hash,
+        Change::Rename {
+            from: file_name.clone(),
+            to: new_name.clone(),
+        },
+    );
+    log_info!(TAG, "Renamed {} to {}", file_name, new_name);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "name": new_name,
+        "updated_collection_hash": updated_collection_hash,
+        "file_hash": file_hash,
+    })))
+}
+
+#[derive(Deserialize)]
+struct CopyFileRequest {
+    group_id: String,
+    repo_id: String,
+    /// Defaults to the source file name.
+    name: Option<String>,
+}
+
+/// Add a file to another repo we own, in this group or another one.
+///
+/// Within a group the existing blob is added to the target collection as is. Groups encrypt
+/// with their own keys, so a copy into another group re-encrypts the local blob instead.
+/// Either way nothing is uploaded again by the client, and only a missing source blob is
+/// fetched from peers.
+#[post("/{file_name}/copy")]
+async fn copy_file(
+    path: web::Path<GroupRepoMediaPath>,
+    body: web::Json<CopyFileRequest>,
+) -> AppResult<impl Responder> {
+    let path_params = path.into_inner();
+    let file_name = &path_params.file_name;
+    let request = body.into_inner();
+    let target_name = request.name.unwrap_or_else(|| file_name.clone());
+    validate_file_name(&target_name)?;
+
+    // Fetch the backend, the source and the target
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+    let target_group = find_group(&backend, &request.group_id).await?;
+    let target_repo = find_repo(&target_group, &request.repo_id).await?;
+    ensure_writable(&target_repo)?;
+
+    let file_hash = find_file_hash(&repo, file_name).await?;
+    if target_repo.get_file_hash(&target_name).await.is_ok() {
+        return Err(ApiError::FileExists(target_name).into());
+    }
+    // Taken before the check so eviction cannot drop the source blob in between.
+    let _writing = storage::blob_write_guard().await;
+    if !group.has_hash(&file_hash).await? {
+        download_from_peers(&group, &file_hash, MEDIA_DOWNLOAD_OVERALL_TIMEOUT).await?;
+    }
+
+    let updated_collection_hash = if group.id() == target_group.id() {
+        target_repo
+            .update_collection(vec![(target_name.clone(), file_hash)], Vec::new())
+            .await
+            .map_err(|e| anyhow!("Failed to copy file: {e}"))?
+    } else {
+        let (_, file_data) = open_decrypted_file(&repo, file_name).await?;
+        let (tx, rx) = mpsc::channel(UPLOAD_CHANNEL_CAPACITY);
+        let forward = async move {
+            let mut file_data = std::pin::pin!(file_data);
+            while let Some(chunk) = file_data.next().await {
+                if tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let (_, uploaded) =
+            futures::join!(forward, target_repo.upload_from_stream(&target_name, rx));
+        uploaded.map_err(|e| anyhow!("Failed to copy file: {e}"))?
+    };
+
+    let target_key = target_repo.id().to_string();
+    let target_hash = find_file_hash(&target_repo, &target_name).await?;
+    metadata::record_copy(
+        &repo.id().to_string(),
+        file_name,
+        &file_hash,
+        &target_key,
+        &target_name,
+        &target_hash,
+    );
+    history::record(
+        &target_key,
+        &updated_collection_hash,
+        Change::Upload {
+            files: vec![target_name.clone()],
+        },
+    );
+    log_info!(
+        TAG,
+        "Copied {} to {} as {}",
+        file_name,
+        target_key,
+        target_name
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "name": target_name,
+        "updated_collection_hash": updated_collection_hash,
+        "file_hash": target_hash,
+    })))
+}
+
 #[post("/{file_name}")]
 async fn upload_file(
+    req: HttpRequest,
     path: web::Path<GroupRepoMediaPath>,
-    mut body: web::Payload,
+    body: web::Payload,
 ) -> AppResult<impl Responder> {
     let path_params = path.into_inner();
     let group_id = &path_params.group_id;
     let repo_id = &path_params.repo_id;
     let file_name = &path_params.file_name;
 
-    // Fetch the backend and group with proper error handling
-    let crypto_key = create_veilid_cryptokey_from_base64(group_id)
-        .map_err(|e| anyhow::anyhow!("Invalid group id: {e}"))?;
-    let backend = get_backend()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to get backend: {e}"))?;
-    let group = backend
-        .get_group(&crypto_key)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to get group: {e}"))?;
+    // Reject declared oversized bodies before reading any of them.
+    let limit = crate::server::config().max_upload_bytes;
+    let declared_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared_length.is_some_and(|length| length > limit) {
+        return Err(ApiError::PayloadTooLarge(limit).into());
+    }
 
-    // Fetch the repo with proper error handling
-    let repo_crypto_key = create_veilid_cryptokey_from_base64(repo_id)
-        .map_err(|e| anyhow::anyhow!("Invalid repo id: {e}"))?;
-    let repo = group
-        .get_repo(&repo_crypto_key)
-        .await
-        .map_err(|e| anyhow::anyhow!("Repo not found: {e}"))?;
+    let declared_mime_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        })
+        .filter(|value| !value.is_empty() && value != "application/octet-stream");
+
+    // Fetch the backend and group
+    let backend = get_backend().await?;
+    let group = find_group(&backend, group_id).await?;
 
-    // Log file_name and stream file content
+    // Fetch the repo
+    let repo = find_repo(&group, repo_id).await?;
+    ensure_writable(&repo)?;
 
     log_info!(TAG, "Uploading file: {}", file_name);
 
-    let mut file_data: Vec<u8> = Vec::new();
-    while let Some(chunk) = body.next().await {
-        let chunk = chunk.map_err(|e| anyhow::anyhow!("Failed to read file chunk: {e}"))?;
-        file_data.extend_from_slice(&chunk);
-    }
+    // Stream the body straight into the blob store so large media never sits in memory.
+    let repo_key = repo.id().to_string();
+    let publish_progress = |bytes_received: u64, done: bool| {
+        events::publish(ServerEvent::UploadProgress {
+            repo_id: repo_key.clone(),
+            file_name: file_name.clone(),
+            bytes_received,
+            done,
+        })
+    };
+    let _writing = storage::blob_write_guard().await;
+    let (tx, rx) = mpsc::channel(UPLOAD_CHANNEL_CAPACITY);
+    let (forwarded, uploaded) = futures::join!(
+        forward_payload(body, tx, limit, |bytes| publish_progress(bytes, false)),
+        repo.upload_from_stream(file_name, rx)
+    );
 
-    // Validate file content
-    if file_data.is_empty() {
-        return Err(anyhow::anyhow!("File content is empty").into());
-    }
+    let uploaded_body = forwarded.map_err(|abort| abort.into_error(limit))?;
+    log_info!(
+        TAG,
+        "Received {} bytes for {}",
+        uploaded_body.size,
+        file_name
+    );
 
-    // Upload the file
-    let updated_collection_hash = repo
-        .upload(file_name, file_data)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to upload file: {e}"))?;
+    let updated_collection_hash =
+        uploaded.map_err(|e| anyhow::anyhow!("Failed to upload file: {e}"))?;
+    publish_progress(uploaded_body.size, true);
+    history::record(
+        &repo_key,
+        &updated_collection_hash,
+        Change::Upload {
+            files: vec![file_name.clone()],
+        },
+    );
 
-    let file_hash = repo
-        .get_file_hash(file_name)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to get file hash: {e}"))?;
+    // Trust an explicit Content-Type from the client; otherwise sniff the content.
+    let mime_type = declared_mime_type
+        .unwrap_or_else(|| metadata::sniff_mime_type(file_name, Some(&uploaded_body.head)));
+    let file_hash = find_file_hash(&repo, file_name).await?;
+    metadata::record_upload(
+        &repo_key,
+        file_name,
+        &file_hash,
+        mime_type,
+        uploaded_body.size,
+    );
 
     Ok(HttpResponse::Ok().json(json!({
         "name": file_name,
@@ -247,3 +885,205 @@ async fn upload_file(
         "file_hash": file_hash,
     })))
 }
+
+/// Outcome of one file in a batch upload.
+#[derive(Debug, Serialize)]
+struct BatchFileResult {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BatchFileResult {
+    fn failed(name: String, error: impl Into<String>) -> Self {
+        BatchFileResult {
+            name,
+            file_hash: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// A batch file that is in the blob store but not yet in the collection.
+struct StagedFile {
+    name: String,
+    hash: Hash,
+    mime_type: String,
+    size: u64,
+}
+
+/// Upload every file part of a `multipart/form-data` body and add them to the collection
+/// together, so the collection hash is published once for the whole batch.
+///
+/// Files that fail are reported in the response and left out of the collection. The upload
+/// cap applies to the request as a whole; going over it rejects the entire batch.
+#[post("")]
+async fn upload_files(
+    path: web::Path<GroupRepoPath>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> AppResult<impl Responder> {
+    let path_params = path.into_inner();
+    let limit = crate::server::config().max_upload_bytes;
+
+    // Checked here rather than by the `Multipart` extractor so the error has the JSON shape.
+    let is_multipart = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<actix_web::mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == "multipart/form-data");
+    if !is_multipart {
+        return Err(ApiError::BadRequest("expected a multipart/form-data body".to_string()).into());
+    }
+    let mut payload = Multipart::new(req.headers(), payload);
+
+    // Fetch the backend and group
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+
+    // Fetch the repo
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+    ensure_writable(&repo)?;
+
+    let repo_key = repo.id().to_string();
+    let publish_progress = |file_name: &str, bytes_received: u64, done: bool| {
+        events::publish(ServerEvent::UploadProgress {
+            repo_id: repo_key.clone(),
+            file_name: file_name.to_string(),
+            bytes_received,
+            done,
+        })
+    };
+
+    let _writing = storage::blob_write_guard().await;
+    let mut results: Vec<BatchFileResult> = Vec::new();
+    let mut staged: Vec<StagedFile> = Vec::new();
+    let mut received: u64 = 0;
+
+    while let Some(field) = payload.next().await {
+        let field =
+            field.map_err(|e| ApiError::BadRequest(format!("Invalid multipart body: {e}")))?;
+        // Plain form fields carry no file; skip them. Skipped parts still count toward the cap.
+        let Some(file_name) = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string)
+        else {
+            received += discard_payload(field, limit - received)
+                .await
+                .map_err(|abort| abort.into_error(limit))?;
+            continue;
+        };
+
+        let rejection = if validate_file_name(&file_name).is_err() {
+            Some("Invalid file name")
+        } else if staged.iter().any(|file| file.name == file_name) {
+            Some("Duplicate file name in batch")
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            received += discard_payload(field, limit - received)
+                .await
+                .map_err(|abort| abort.into_error(limit))?;
+            results.push(BatchFileResult::failed(file_name, rejection));
+            continue;
+        }
+
+        log_info!(TAG, "Uploading batch file: {}", file_name);
+        let declared_mime_type = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_string())
+            .filter(|value| value != "application/octet-stream");
+
+        let (tx, rx) = mpsc::channel(UPLOAD_CHANNEL_CAPACITY);
+        let (forwarded, uploaded) = futures::join!(
+            forward_payload(field, tx, limit - received, |bytes| {
+                publish_progress(&file_name, bytes, false)
+            }),
+            repo.upload_blob_from_stream(rx)
+        );
+
+        let uploaded_body = match forwarded {
+            Ok(uploaded_body) => uploaded_body,
+            Err(UploadAbort::Empty) => {
+                results.push(BatchFileResult::failed(file_name, "File content is empty"));
+                continue;
+            }
+            // Over the cap, or the rest of the body cannot be read either.
+            Err(abort) => return Err(abort.into_error(limit)),
+        };
+        // Counted even if storing the file fails; every byte read takes up the cap.
+        received += uploaded_body.size;
+        let hash = match uploaded {
+            Ok(hash) => hash,
+            Err(e) => {
+                log_error!(TAG, "Failed to upload batch file {}: {}", file_name, e);
+                results.push(BatchFileResult::failed(
+                    file_name,
+                    format!("Failed to upload file: {e}"),
+                ));
+                continue;
+            }
+        };
+
+        let mime_type = declared_mime_type
+            .unwrap_or_else(|| metadata::sniff_mime_type(&file_name, Some(&uploaded_body.head)));
+        results.push(BatchFileResult {
+            name: file_name.clone(),
+            file_hash: Some(hash),
+            error: None,
+        });
+        staged.push(StagedFile {
+            name: file_name,
+            hash,
+            mime_type,
+            size: uploaded_body.size,
+        });
+    }
+
+    if results.is_empty() {
+        return Err(ApiError::BadRequest("No files in multipart body".to_string()).into());
+    }
+
+    let updated_collection_hash = if staged.is_empty() {
+        None
+    } else {
+        let additions = staged
+            .iter()
+            .map(|file| (file.name.clone(), file.hash))
+            .collect();
+        let collection_hash = repo
+            .update_collection(additions, Vec::new())
+            .await
+            .map_err(|e| anyhow!("Failed to add uploaded files to collection: {e}"))?;
+        history::record(
+            &repo_key,
+            &collection_hash,
+            Change::Upload {
+                files: staged.iter().map(|file| file.name.clone()).collect(),
+            },
+        );
+        Some(collection_hash)
+    };
+    log_info!(
+        TAG,
+        "Uploaded {} of {} batch files to {}",
+        staged.len(),
+        results.len(),
+        repo_key
+    );
+
+    for file in staged {
+        metadata::record_upload(&repo_key, &file.name, &file.hash, file.mime_type, file.size);
+        publish_progress(&file.name, file.size, true);
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "updated_collection_hash": updated_collection_hash,
+        "files": results,
+    })))
+}
@@ -0,0 +1,128 @@
+use crate::constants::TAG;
+use crate::error::AppResult;
+use crate::log_debug;
+use crate::models::GroupPath;
+use crate::server::{ensure_backend_ready, get_backend};
+use crate::sync_state;
+use crate::utils::find_group;
+use actix_web::{get, web, HttpResponse, Responder, Scope};
+use futures::{future, stream, StreamExt};
+use save_dweb_backend::common::DHTEntity;
+use save_dweb_backend::group::Group;
+use save_dweb_backend::repo::Repo;
+use serde::Serialize;
+use serde_json::json;
+use std::time::Duration;
+
+// Members looked up at once; each may wait on the DHT for its collection hash.
+const MEMBER_LOOKUP_CONCURRENCY: usize = 4;
+
+// Local hash checks in flight at once per member.
+const FILE_LOOKUP_CONCURRENCY: usize = 16;
+
+const COLLECTION_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn scope() -> Scope {
+    web::scope("/members").service(list_members)
+}
+
+/// A group member, i.e. the repo they write to, and how far we have synced it.
+#[derive(Debug, Serialize)]
+pub struct Member {
+    pub repo_id: String,
+    pub name: String,
+    pub is_self: bool,
+    pub collection_hash: Option<String>,
+    /// When a refresh last saw the collection change. `None` if no refresh has seen the
+    /// current collection yet.
+    pub changed_at: Option<u64>,
+    /// `None` while the member's collection is not available locally.
+    pub file_count: Option<usize>,
+    pub local_file_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("")]
+async fn list_members(path: web::Path<GroupPath>) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path.group_id).await?;
+
+    let repos: Vec<Repo> = group.repos.lock().await.values().cloned().collect();
+    let mut members: Vec<Member> = stream::iter(repos)
+        .map(|repo| member_status(&group, repo))
+        .buffered(MEMBER_LOOKUP_CONCURRENCY)
+        .collect()
+        .await;
+    members.sort_by(|a, b| b.is_self.cmp(&a.is_self).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(HttpResponse::Ok().json(json!({ "members": members })))
+}
+
+async fn member_status(group: &Group, repo: Repo) -> Member {
+    let repo_id = repo.id().to_string();
+    let snapshot = sync_state::last_synced(&repo_id);
+    let mut member = Member {
+        repo_id,
+        name: repo
+            .get_name()
+            .await
+            .unwrap_or_else(|_| "Unknown".to_string()),
+        is_self: repo.can_write(),
+        collection_hash: None,
+        changed_at: None,
+        file_count: None,
+        local_file_count: None,
+        error: None,
+    };
+
+    match tokio::time::timeout(COLLECTION_LOOKUP_TIMEOUT, repo.get_hash_from_dht()).await {
+        Ok(Ok(hash)) => member.collection_hash = Some(hash.to_string()),
+        Ok(Err(e)) => member.error = Some(format!("Error getting repo hash from DHT: {e}")),
+        Err(_) => member.error = Some("Timed out getting repo hash from DHT".to_string()),
+    }
+    // Fall back to what the last refresh saw while the DHT is unreachable.
+    if let Some(snapshot) = snapshot {
+        if member.collection_hash.is_none() {
+            member.collection_hash = snapshot.collection_hash.clone();
+        }
+        if member.collection_hash.is_some() && member.collection_hash == snapshot.collection_hash {
+            member.changed_at = Some(snapshot.synced_at);
+        }
+    }
+
+    let files = match repo.list_files().await {
+        Ok(files) => files,
+        Err(e) => {
+            log_debug!(
+                TAG,
+                "Error listing files of member {}: {}",
+                member.repo_id,
+                e
+            );
+            return member;
+        }
+    };
+    member.file_count = Some(files.len());
+    // A repo we own that has no files has never published a collection.
+    if member.is_self && files.is_empty() {
+        member.error = None;
+    }
+
+    let repo = &repo;
+    let local_file_count = stream::iter(files)
+        .map(|name| async move {
+            match repo.get_file_hash(&name).await {
+                Ok(hash) => group.has_hash(&hash).await.unwrap_or(false),
+                Err(_) => false,
+            }
+        })
+        .buffer_unordered(FILE_LOOKUP_CONCURRENCY)
+        .filter(|is_local| future::ready(*is_local))
+        .count()
+        .await;
+    member.local_file_count = Some(local_file_count);
+
+    member
+}
@@ -0,0 +1,280 @@
+use crate::utils::{load_json_state, save_json_state, unix_timestamp};
+use iroh_blobs::Hash;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const METADATA_FILE: &str = "media-metadata.json";
+
+// Views only order eviction, so they are written out at most this often instead of on every
+// download and Range request. Any other change saves them sooner.
+const VIEW_SAVE_DELAY: Duration = Duration::from_secs(30);
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Bytes of a file needed to recognise every signature in `sniff_magic_bytes`.
+pub const SNIFF_LENGTH: usize = 512;
+
+/// Per-file details the backend collection does not carry.
+///
+/// Recorded when we upload a file, and filled in lazily for other members' files the
+/// first time we serve them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    pub mime_type: Option<String>,
+    pub size: Option<u64>,
+    pub uploaded_at: Option<u64>,
+    /// When the file was last served or fetched, used to pick files to evict.
+    pub last_viewed_at: Option<u64>,
+    /// Content the details describe. A name overwritten since then has a different hash,
+    /// and its details are ignored. Unset in entries recorded before hashes were kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
+}
+
+impl FileMetadata {
+    fn describes(&self, file_hash: &Hash) -> bool {
+        self.file_hash
+            .as_ref()
+            .is_none_or(|recorded| *recorded == file_hash.to_string())
+    }
+}
+
+// Keyed by `metadata_key(repo_id, file_name)`.
+static METADATA: Lazy<Mutex<HashMap<String, FileMetadata>>> =
+    Lazy::new(|| Mutex::new(load_json_state(METADATA_FILE)));
+
+static VIEW_SAVE_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+fn metadata_key(repo_id: &str, file_name: &str) -> String {
+    format!("{repo_id}/{file_name}")
+}
+
+/// The entry for `file_name` as it is now, starting over when it describes other content.
+fn current_entry<'a>(
+    metadata: &'a mut HashMap<String, FileMetadata>,
+    repo_id: &str,
+    file_name: &str,
+    file_hash: &Hash,
+) -> &'a mut FileMetadata {
+    let entry = metadata
+        .entry(metadata_key(repo_id, file_name))
+        .or_default();
+    if !entry.describes(file_hash) {
+        *entry = FileMetadata::default();
+    }
+    entry.file_hash = Some(file_hash.to_string());
+    entry
+}
+
+fn update<F>(repo_id: &str, file_name: &str, file_hash: &Hash, f: F)
+where
+    F: FnOnce(&mut FileMetadata),
+{
+    let Ok(mut metadata) = METADATA.lock() else {
+        return;
+    };
+    f(current_entry(&mut metadata, repo_id, file_name, file_hash));
+    save_json_state(METADATA_FILE, &*metadata);
+}
+
+fn with_guessed_mime_type(mut file_metadata: FileMetadata, file_name: &str) -> FileMetadata {
+    if file_metadata.mime_type.is_none() {
+        file_metadata.mime_type = Some(sniff_mime_type(file_name, None));
+    }
+    file_metadata
+}
+
+/// Stored metadata for a file, with the MIME type guessed from its name when unknown.
+///
+/// Used where the file's hash is not looked up yet, so the details may describe content
+/// that was overwritten since. Prefer `get_current` when the hash is known.
+pub fn get(repo_id: &str, file_name: &str) -> FileMetadata {
+    let file_metadata = METADATA
+        .lock()
+        .ok()
+        .and_then(|metadata| metadata.get(&metadata_key(repo_id, file_name)).cloned())
+        .unwrap_or_default();
+    with_guessed_mime_type(file_metadata, file_name)
+}
+
+/// Like `get`, ignoring details recorded for content other than `file_hash`.
+pub fn get_current(repo_id: &str, file_name: &str, file_hash: &Hash) -> FileMetadata {
+    let file_metadata = METADATA
+        .lock()
+        .ok()
+        .and_then(|metadata| metadata.get(&metadata_key(repo_id, file_name)).cloned())
+        .filter(|entry| entry.describes(file_hash))
+        .unwrap_or_default();
+    with_guessed_mime_type(file_metadata, file_name)
+}
+
+/// Whether a MIME type was recorded or sniffed from the content `file_hash`, rather than
+/// only guessed.
+pub fn has_mime_type(repo_id: &str, file_name: &str, file_hash: &Hash) -> bool {
+    METADATA
+        .lock()
+        .ok()
+        .and_then(|metadata| {
+            metadata
+                .get(&metadata_key(repo_id, file_name))
+                .map(|entry| entry.describes(file_hash) && entry.mime_type.is_some())
+        })
+        .unwrap_or(false)
+}
+
+pub fn record_upload(
+    repo_id: &str,
+    file_name: &str,
+    file_hash: &Hash,
+    mime_type: String,
+    size: u64,
+) {
+    update(repo_id, file_name, file_hash, |entry| {
+        *entry = FileMetadata {
+            mime_type: Some(mime_type),
+            size: Some(size),
+            uploaded_at: Some(unix_timestamp()),
+            last_viewed_at: None,
+            file_hash: Some(file_hash.to_string()),
+        };
+    });
+}
+
+/// Remember details learned while serving a file we did not upload ourselves.
+pub fn record_download(
+    repo_id: &str,
+    file_name: &str,
+    file_hash: &Hash,
+    mime_type: Option<String>,
+    size: u64,
+) {
+    update(repo_id, file_name, file_hash, |entry| {
+        if mime_type.is_some() {
+            entry.mime_type = mime_type;
+        }
+        entry.size = Some(size);
+    });
+}
+
+/// Drop the details of a file whose content a refresh saw change, unless they were
+/// recorded for the new content `file_hash` already.
+pub fn forget_if_changed(repo_id: &str, file_name: &str, file_hash: &str) {
+    let Ok(mut metadata) = METADATA.lock() else {
+        return;
+    };
+    let key = metadata_key(repo_id, file_name);
+    let stale = metadata
+        .get(&key)
+        .is_some_and(|entry| entry.file_hash.as_deref() != Some(file_hash));
+    if stale {
+        metadata.remove(&key);
+        save_json_state(METADATA_FILE, &*metadata);
+    }
+}
+
+/// Note that a file was just served. Kept in memory and saved with a delay.
+pub fn record_view(repo_id: &str, file_name: &str, file_hash: &Hash) {
+    {
+        let Ok(mut metadata) = METADATA.lock() else {
+            return;
+        };
+        current_entry(&mut metadata, repo_id, file_name, file_hash).last_viewed_at =
+            Some(unix_timestamp());
+    }
+
+    if VIEW_SAVE_SCHEDULED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            runtime.spawn(async {
+                tokio::time::sleep(VIEW_SAVE_DELAY).await;
+                let _ = tokio::task::spawn_blocking(save_views).await;
+            });
+        }
+        Err(_) => save_views(),
+    }
+}
+
+fn save_views() {
+    VIEW_SAVE_SCHEDULED.store(false, Ordering::SeqCst);
+    if let Ok(metadata) = METADATA.lock() {
+        save_json_state(METADATA_FILE, &*metadata);
+    }
+}
+
+/// Carry a file's metadata over to its new name.
+pub fn rename(repo_id: &str, from: &str, to: &str) {
+    let Ok(mut metadata) = METADATA.lock() else {
+        return;
+    };
+    if let Some(entry) = metadata.remove(&metadata_key(repo_id, from)) {
+        metadata.insert(metadata_key(repo_id, to), entry);
+        save_json_state(METADATA_FILE, &*metadata);
+    }
+}
+
+/// Record a copy of a file as a fresh upload with the source's type and size.
+pub fn record_copy(
+    from_repo_id: &str,
+    from: &str,
+    from_hash: &Hash,
+    to_repo_id: &str,
+    to: &str,
+    to_hash: &Hash,
+) {
+    let source = get_current(from_repo_id, from, from_hash);
+    update(to_repo_id, to, to_hash, |entry| {
+        *entry = FileMetadata {
+            mime_type: source.mime_type,
+            size: source.size,
+            uploaded_at: Some(unix_timestamp()),
+            last_viewed_at: None,
+            file_hash: Some(to_hash.to_string()),
+        };
+    });
+}
+
+pub fn remove(repo_id: &str, file_name: &str) {
+    let Ok(mut metadata) = METADATA.lock() else {
+        return;
+    };
+    if metadata.remove(&metadata_key(repo_id, file_name)).is_some() {
+        save_json_state(METADATA_FILE, &*metadata);
+    }
+}
+
+/// Pick a MIME type from the leading bytes of the content, falling back to the extension.
+pub fn sniff_mime_type(file_name: &str, head: Option<&[u8]>) -> String {
+    if let Some(mime_type) = head.and_then(sniff_magic_bytes) {
+        return mime_type.to_string();
+    }
+
+    mime_guess::from_path(file_name)
+        .first_raw()
+        .unwrap_or(DEFAULT_MIME_TYPE)
+        .to_string()
+}
+
+fn sniff_magic_bytes(head: &[u8]) -> Option<&'static str> {
+    let mime_type = match head {
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => "image/png",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => "audio/wav",
+        [_, _, _, _, b'f', b't', b'y', b'p', b'h', b'e', b'i', b'c', ..] => "image/heic",
+        [_, _, _, _, b'f', b't', b'y', b'p', b'q', b't', ..] => "video/quicktime",
+        [_, _, _, _, b'f', b't', b'y', b'p', b'M', b'4', b'A', ..] => "audio/mp4",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => "video/mp4",
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => "video/webm",
+        [b'I', b'D', b'3', ..] => "audio/mpeg",
+        [b'O', b'g', b'g', b'S', ..] => "audio/ogg",
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        _ => return None,
+    };
+    Some(mime_type)
+}
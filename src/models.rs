@@ -39,6 +39,12 @@ pub struct SnowbirdFile {
     pub name: String,
     pub hash: Hash,
     pub is_downloaded: bool,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub uploaded_at: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -46,7 +52,9 @@ pub struct SnowbirdGroup {
     pub key: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    pub uri: String,
+    /// Group URL including its secret; only filled in for invite responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
 }
 
 impl From<&Group> for SnowbirdGroup {
@@ -54,7 +62,7 @@ impl From<&Group> for SnowbirdGroup {
         SnowbirdGroup {
             key: group.id().to_string(),
             name: None,
-            uri: group.get_url().unwrap_or_default(),
+            uri: None,
         }
     }
 }
@@ -0,0 +1,486 @@
+use crate::constants::TAG;
+use crate::error::{ApiError, AppError, AppResult};
+use crate::events::{self, ServerEvent};
+use crate::metadata;
+use crate::models::{GroupPath, GroupRepoPath};
+use crate::server::{ensure_backend_ready, get_backend};
+use crate::utils::{
+    create_veilid_cryptokey_from_base64, download_from_peers, fetch_file_from_peers, find_group,
+    find_repo, load_json_state, save_json_state, unix_timestamp,
+};
+use crate::{log_debug, log_error, log_info};
+use actix_web::{delete, get, post, web, HttpResponse, Responder, Scope};
+use iroh_blobs::Hash;
+use once_cell::sync::Lazy;
+use save_dweb_backend::group::Group;
+use save_dweb_backend::repo::Repo;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, Semaphore};
+
+const PINS_FILE: &str = "pins.json";
+
+// Pins sync in the background, so like download jobs they can wait a long time for peers.
+const PIN_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+// Pinned content is re-checked this often even when no DHT change was seen.
+const PIN_RESYNC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Pins download one at a time so they do not starve on-demand downloads of peers.
+const MAX_CONCURRENT_PIN_SYNCS: usize = 1;
+
+/// A group, or a single repo in it, that is kept fully downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    pub group_id: String,
+    pub repo_id: Option<String>,
+    pub pinned_at: u64,
+}
+
+impl Pin {
+    fn key(&self) -> String {
+        pin_key(&self.group_id, self.repo_id.as_deref())
+    }
+
+    fn covers(&self, group_id: &str, repo_id: &str) -> bool {
+        self.group_id == group_id && self.repo_id.as_deref().is_none_or(|id| id == repo_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinState {
+    Syncing,
+    Synced,
+    Failed,
+}
+
+/// Progress of a pin as reported by the pin endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct PinStatus {
+    #[serde(flatten)]
+    pub pin: Pin,
+    pub state: PinState,
+    pub total_files: usize,
+    pub missing_files: usize,
+    /// Bytes still to download. `None` while any missing file's size is unknown, which is
+    /// usual for other members' files that were never downloaded.
+    pub missing_bytes: Option<u64>,
+    /// Missing files whose size is only learned once they are downloaded.
+    pub missing_files_unknown_size: usize,
+    pub last_synced_at: Option<u64>,
+    pub error: Option<String>,
+    /// Size of the missing files whose size is known.
+    #[serde(skip)]
+    known_missing_bytes: u64,
+}
+
+impl PinStatus {
+    fn new(pin: Pin) -> Self {
+        PinStatus {
+            pin,
+            state: PinState::Syncing,
+            total_files: 0,
+            missing_files: 0,
+            missing_bytes: None,
+            missing_files_unknown_size: 0,
+            last_synced_at: None,
+            error: None,
+            known_missing_bytes: 0,
+        }
+    }
+
+    fn set_missing(&mut self, missing: &[MissingBlob]) {
+        self.missing_files = missing.len();
+        self.known_missing_bytes = missing.iter().filter_map(|blob| blob.size).sum();
+        self.missing_files_unknown_size = missing.iter().filter(|blob| blob.size.is_none()).count();
+        self.update_missing_bytes();
+    }
+
+    fn mark_downloaded(&mut self, blob: &MissingBlob) {
+        self.missing_files = self.missing_files.saturating_sub(1);
+        match blob.size {
+            Some(size) => self.known_missing_bytes = self.known_missing_bytes.saturating_sub(size),
+            None => {
+                self.missing_files_unknown_size = self.missing_files_unknown_size.saturating_sub(1)
+            }
+        }
+        self.update_missing_bytes();
+    }
+
+    fn update_missing_bytes(&mut self) {
+        self.missing_bytes =
+            (self.missing_files_unknown_size == 0).then_some(self.known_missing_bytes);
+    }
+}
+
+// Keyed by `pin_key(group_id, repo_id)`.
+static PINS: Lazy<Mutex<HashMap<String, Pin>>> =
+    Lazy::new(|| Mutex::new(load_json_state(PINS_FILE)));
+
+// Latest sync progress per pin; rebuilt by the first sync after a restart.
+static STATUS: Lazy<Mutex<HashMap<String, PinStatus>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Pins with a sync task running, and whether another pass was requested meanwhile.
+static SYNCING: Lazy<Mutex<HashMap<String, bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static SYNC_SLOTS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_PIN_SYNCS);
+
+pub fn group_scope() -> Scope {
+    web::scope("/pin")
+        .service(pin_group)
+        .service(get_group_pin)
+        .service(unpin_group)
+}
+
+pub fn repo_scope() -> Scope {
+    web::scope("/pin")
+        .service(pin_repo)
+        .service(get_repo_pin)
+        .service(unpin_repo)
+}
+
+fn pin_key(group_id: &str, repo_id: Option<&str>) -> String {
+    match repo_id {
+        Some(repo_id) => format!("{group_id}/{repo_id}"),
+        None => group_id.to_string(),
+    }
+}
+
+/// Pin key for IDs from a request path, which may use the legacy untyped key format.
+fn request_pin_key(group_id: &str, repo_id: Option<&str>) -> AppResult<String> {
+    let group_id = create_veilid_cryptokey_from_base64(group_id)?.to_string();
+    let repo_id = repo_id
+        .map(|repo_id| create_veilid_cryptokey_from_base64(repo_id).map(|key| key.to_string()))
+        .transpose()?;
+    Ok(pin_key(&group_id, repo_id.as_deref()))
+}
+
+fn pins() -> Vec<Pin> {
+    PINS.lock()
+        .map(|pins| pins.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Whether a pin keeps this repo downloaded, either directly or through its group.
+pub fn is_repo_pinned(group_id: &str, repo_id: &str) -> bool {
+    PINS.lock()
+        .is_ok_and(|pins| pins.values().any(|pin| pin.covers(group_id, repo_id)))
+}
+
+fn is_pinned(key: &str) -> bool {
+    PINS.lock().is_ok_and(|pins| pins.contains_key(key))
+}
+
+/// Persist a pin and start syncing it. Pinning again keeps the original `pinned_at`.
+fn add(group_id: String, repo_id: Option<String>) -> PinStatus {
+    let key = pin_key(&group_id, repo_id.as_deref());
+    let pin = {
+        let mut pins = PINS.lock().unwrap_or_else(|e| e.into_inner());
+        let pin = pins
+            .entry(key.clone())
+            .or_insert_with(|| Pin {
+                group_id,
+                repo_id,
+                pinned_at: unix_timestamp(),
+            })
+            .clone();
+        save_json_state(PINS_FILE, &*pins);
+        pin
+    };
+    log_info!(TAG, "Pinned {}", key);
+
+    schedule_sync(pin.clone());
+    status(&key).unwrap_or_else(|| PinStatus::new(pin))
+}
+
+/// Drop a pin. Blobs already downloaded stay in the store.
+fn remove(key: &str) -> bool {
+    let removed = {
+        let mut pins = PINS.lock().unwrap_or_else(|e| e.into_inner());
+        let removed = pins.remove(key).is_some();
+        if removed {
+            save_json_state(PINS_FILE, &*pins);
+        }
+        removed
+    };
+    if let Ok(mut status) = STATUS.lock() {
+        status.remove(key);
+    }
+    if removed {
+        log_info!(TAG, "Unpinned {}", key);
+    }
+    removed
+}
+
+/// Drop every pin in a group, used when the group itself is deleted.
+pub fn remove_group(group_id: &str) {
+    let keys: Vec<String> = pins()
+        .into_iter()
+        .filter(|pin| pin.group_id == group_id)
+        .map(|pin| pin.key())
+        .collect();
+    for key in keys {
+        remove(&key);
+    }
+}
+
+fn status(key: &str) -> Option<PinStatus> {
+    let pin = PINS.lock().ok()?.get(key).cloned()?;
+    let status = STATUS.lock().ok()?.get(key).cloned();
+    Some(status.unwrap_or_else(|| PinStatus::new(pin)))
+}
+
+fn update_status<F>(pin: &Pin, f: F)
+where
+    F: FnOnce(&mut PinStatus),
+{
+    if !is_pinned(&pin.key()) {
+        return;
+    }
+    let Ok(mut status) = STATUS.lock() else {
+        return;
+    };
+    f(status
+        .entry(pin.key())
+        .or_insert_with(|| PinStatus::new(pin.clone())));
+}
+
+/// Sync `pin` in the background. If it is already syncing, it runs once more afterwards
+/// so files published during the current pass are picked up.
+fn schedule_sync(pin: Pin) {
+    let key = pin.key();
+    {
+        let mut syncing = SYNCING.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rerun) = syncing.get_mut(&key) {
+            *rerun = true;
+            return;
+        }
+        syncing.insert(key.clone(), false);
+    }
+
+    tokio::spawn(async move {
+        loop {
+            if is_pinned(&key) {
+                if let Ok(_slot) = SYNC_SLOTS.acquire().await {
+                    run_sync(&pin).await;
+                }
+            }
+
+            let mut syncing = SYNCING.lock().unwrap_or_else(|e| e.into_inner());
+            match syncing.get_mut(&key) {
+                Some(rerun) if *rerun => *rerun = false,
+                _ => {
+                    syncing.remove(&key);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Sync every pin now, then again whenever a pinned repo changes in the DHT and every
+/// `PIN_RESYNC_INTERVAL`, so newly published files are downloaded as they appear. Runs
+/// until `server::shutdown` aborts it.
+pub async fn keep_pins_synced() {
+    let mut updates = events::subscribe();
+    let mut resync = tokio::time::interval(PIN_RESYNC_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = resync.tick() => pins().into_iter().for_each(schedule_sync),
+            update = updates.recv() => match update {
+                Ok(ServerEvent::DhtValueChange { group_id, repo_id }) => pins()
+                    .into_iter()
+                    .filter(|pin| pin.covers(&group_id, &repo_id))
+                    .for_each(schedule_sync),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    pins().into_iter().for_each(schedule_sync)
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+struct MissingBlob {
+    repo_id: String,
+    file_name: String,
+    hash: Hash,
+    size: Option<u64>,
+}
+
+async fn run_sync(pin: &Pin) {
+    log_debug!(TAG, "Syncing pin {}", pin.key());
+    update_status(pin, |status| status.state = PinState::Syncing);
+
+    let result = sync_pin(pin).await;
+
+    update_status(pin, |status| match result {
+        Ok(()) => {
+            status.state = PinState::Synced;
+            status.error = None;
+            status.last_synced_at = Some(unix_timestamp());
+        }
+        Err(e) => {
+            log_error!(TAG, "Failed to sync pin {}: {}", pin.key(), e);
+            status.state = PinState::Failed;
+            status.error = Some(e.to_string());
+        }
+    });
+}
+
+/// Download every blob of the pinned repos that is not stored locally yet.
+///
+/// One unreachable repo or blob does not stop the rest; the first failure is returned. A
+/// repo whose peers time out on one blob is skipped for the rest of the pass, since they
+/// would most likely time out on every other blob too and hold up the other pins.
+async fn sync_pin(pin: &Pin) -> AppResult<()> {
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &pin.group_id).await?;
+    let repos: Vec<Repo> = match &pin.repo_id {
+        Some(repo_id) => vec![*find_repo(&group, repo_id).await?],
+        None => group.repos.lock().await.values().cloned().collect(),
+    };
+
+    let mut first_error: Option<AppError> = None;
+    let mut total_files = 0;
+    let mut missing = Vec::new();
+    for repo in &repos {
+        match find_missing_blobs(&group, repo, &mut missing).await {
+            Ok(file_count) => total_files += file_count,
+            Err(e) => {
+                log_debug!(TAG, "Could not list pinned repo {}: {}", repo.id(), e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    update_status(pin, |status| {
+        status.total_files = total_files;
+        status.set_missing(&missing);
+    });
+
+    let mut timed_out_repos = HashSet::new();
+    for blob in missing {
+        if !is_pinned(&pin.key()) {
+            return Ok(());
+        }
+        if timed_out_repos.contains(&blob.repo_id) {
+            continue;
+        }
+        let fetched = fetch_file_from_peers(
+            &group,
+            &blob.repo_id,
+            &blob.file_name,
+            &blob.hash,
+            PIN_DOWNLOAD_TIMEOUT,
+        )
+        .await;
+        match fetched {
+            Ok(()) => update_status(pin, |status| status.mark_downloaded(&blob)),
+            Err(e) => {
+                log_debug!(TAG, "Could not download pinned blob {}: {}", blob.hash, e);
+                if matches!(e.api_error(), Some(ApiError::PeerTimeout(_))) {
+                    log_info!(
+                        TAG,
+                        "Peers of pinned repo {} timed out; retrying it on the next sync",
+                        blob.repo_id
+                    );
+                    timed_out_repos.insert(blob.repo_id.clone());
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Add the repo's files that are not stored locally to `missing`, returning how many files
+/// the repo has. Other members' collections are fetched first so their files can be listed.
+async fn find_missing_blobs(
+    group: &Group,
+    repo: &Repo,
+    missing: &mut Vec<MissingBlob>,
+) -> AppResult<usize> {
+    if !repo.can_write() {
+        let collection_hash = repo.get_hash_from_dht().await?;
+        if !group.has_hash(&collection_hash).await? {
+            download_from_peers(group, &collection_hash, PIN_DOWNLOAD_TIMEOUT).await?;
+        }
+    }
+
+    let repo_key = repo.id().to_string();
+    let files = repo.list_files().await?;
+    for file_name in &files {
+        let hash = repo.get_file_hash(file_name).await?;
+        if !group.has_hash(&hash).await? {
+            missing.push(MissingBlob {
+                repo_id: repo_key.clone(),
+                file_name: file_name.clone(),
+                hash,
+                size: metadata::get_current(&repo_key, file_name, &hash).size,
+            });
+        }
+    }
+
+    Ok(files.len())
+}
+
+#[post("")]
+async fn pin_group(path: web::Path<GroupPath>) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path.group_id).await?;
+
+    let status = add(group.id().to_string(), None);
+    Ok(HttpResponse::Accepted().json(status))
+}
+
+#[get("")]
+async fn get_group_pin(path: web::Path<GroupPath>) -> AppResult<impl Responder> {
+    let key = request_pin_key(&path.group_id, None)?;
+    let status = status(&key).ok_or(ApiError::PinNotFound(key))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[delete("")]
+async fn unpin_group(path: web::Path<GroupPath>) -> AppResult<impl Responder> {
+    let key = request_pin_key(&path.group_id, None)?;
+    if !remove(&key) {
+        return Err(ApiError::PinNotFound(key).into());
+    }
+    Ok(HttpResponse::Ok().json(json!({})))
+}
+
+#[post("")]
+async fn pin_repo(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path.group_id).await?;
+    let repo = find_repo(&group, &path.repo_id).await?;
+
+    let status = add(group.id().to_string(), Some(repo.id().to_string()));
+    Ok(HttpResponse::Accepted().json(status))
+}
+
+#[get("")]
+async fn get_repo_pin(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
+    let key = request_pin_key(&path.group_id, Some(&path.repo_id))?;
+    let status = status(&key).ok_or(ApiError::PinNotFound(key))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[delete("")]
+async fn unpin_repo(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
+    let key = request_pin_key(&path.group_id, Some(&path.repo_id))?;
+    if !remove(&key) {
+        return Err(ApiError::PinNotFound(key).into());
+    }
+    Ok(HttpResponse::Ok().json(json!({})))
+}
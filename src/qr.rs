@@ -0,0 +1,48 @@
+use crate::error::{ApiError, AppResult};
+use image::{ImageFormat, Luma};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use std::io::Cursor;
+
+// Smallest rendered side in pixels, large enough for phone cameras to read from a screen.
+const MIN_QR_DIMENSION: u32 = 512;
+
+/// Render `data` as an SVG QR code.
+pub fn render_svg(data: &str) -> AppResult<String> {
+    let code = QrCode::new(data.as_bytes()).map_err(anyhow::Error::new)?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(MIN_QR_DIMENSION, MIN_QR_DIMENSION)
+        .build())
+}
+
+/// Render `data` as a PNG QR code.
+pub fn render_png(data: &str) -> AppResult<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes()).map_err(anyhow::Error::new)?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(MIN_QR_DIMENSION, MIN_QR_DIMENSION)
+        .build();
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(anyhow::Error::new)?;
+    Ok(png)
+}
+
+/// Read the text of the first decodable QR code in a PNG or JPEG image.
+///
+/// CPU heavy for camera photos; call it through `web::block`.
+pub fn decode(image_data: &[u8]) -> AppResult<String> {
+    let image = image::load_from_memory(image_data)
+        .map_err(|e| ApiError::BadRequest(format!("Unreadable image: {e}")))?
+        .to_luma8();
+
+    let mut prepared = rqrr::PreparedImage::prepare(image);
+    prepared
+        .detect_grids()
+        .into_iter()
+        .find_map(|grid| grid.decode().ok().map(|(_, content)| content))
+        .ok_or_else(|| ApiError::BadRequest("No QR code found in image".to_string()).into())
+}
@@ -0,0 +1,69 @@
+//! Scrubbing of group key material from text that may end up in logs.
+
+use serde_json::Value;
+
+/// Group URL parameters that carry key material.
+const SECRET_PARAMS: [&str; 3] = ["pk", "sk", "enc"];
+
+/// JSON fields that hold a whole group URL.
+const SECRET_FIELDS: [&str; 3] = ["uri", "url", "group_url"];
+
+const REDACTED: &str = "<redacted>";
+
+/// Replace the values of key-carrying URL parameters anywhere in `text`.
+pub fn redact(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut at_param_start = true;
+
+    while let Some(c) = rest.chars().next() {
+        if at_param_start {
+            let secret = SECRET_PARAMS.iter().find(|name| {
+                rest.strip_prefix(**name)
+                    .is_some_and(|after| after.starts_with('='))
+            });
+            if let Some(name) = secret {
+                let value = &rest[name.len() + 1..];
+                let value_len = value.find(is_value_end).unwrap_or(value.len());
+                redacted.push_str(name);
+                redacted.push('=');
+                redacted.push_str(REDACTED);
+                rest = &value[value_len..];
+                at_param_start = false;
+                continue;
+            }
+        }
+
+        redacted.push(c);
+        at_param_start = matches!(c, '?' | '&' | '#' | ';');
+        rest = &rest[c.len_utf8()..];
+    }
+
+    redacted
+}
+
+/// Copy of a JSON body with group URL fields replaced and URL parameters scrubbed.
+pub fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| {
+                    let field = if SECRET_FIELDS.contains(&name.as_str()) && field.is_string() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact_json(field)
+                    };
+                    (name.clone(), field)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_json).collect()),
+        Value::String(text) => Value::String(redact(text)),
+        other => other.clone(),
+    }
+}
+
+fn is_value_end(c: char) -> bool {
+    matches!(c, '&' | '#' | ';' | '"' | '\'') || c.is_whitespace()
+}
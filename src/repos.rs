@@ -1,11 +1,14 @@
 use crate::constants::TAG;
 use crate::error::{AppError, AppResult};
+use crate::export;
+use crate::history;
 use crate::log_debug;
 use crate::media;
-use crate::models::{AsyncFrom, GroupPath, GroupRepoPath, SnowbirdRepo};
+use crate::models::{AsyncFrom, GroupPath, GroupRepoPath, RequestName, SnowbirdRepo};
+use crate::pins;
 use crate::server::{ensure_backend_ready, get_backend};
-use crate::utils::create_veilid_cryptokey_from_base64;
-use actix_web::{get, post, web, HttpResponse, Responder, Scope};
+use crate::utils::{ensure_writable, find_group, find_repo, validate_name};
+use actix_web::{get, patch, post, web, HttpResponse, Responder, Scope};
 use anyhow::Result;
 use save_dweb_backend::common::DHTEntity;
 use save_dweb_backend::group::Group;
@@ -19,6 +22,11 @@ pub fn scope() -> Scope {
         .service(
             web::scope("/{repo_id}")
                 .service(get_repo)
+                .service(rename_repo)
+                .service(pins::repo_scope())
+                .service(history::scope())
+                .service(history::diff_scope())
+                .service(export::export_repo)
                 .service(media::scope()),
         )
 }
@@ -36,9 +44,8 @@ async fn list_repos(path: web::Path<GroupPath>) -> AppResult<impl Responder> {
     log_debug!(TAG, "group_id = {}", group_id);
 
     // Fetch the backend and the group
-    let crypto_key = create_veilid_cryptokey_from_base64(group_id)?;
     let backend = get_backend().await?;
-    let group = backend.get_group(&crypto_key).await?;
+    let group = find_group(&backend, group_id).await?;
     log_debug!(TAG, "got group");
 
     let snowbird_repos = get_snowbird_repos(&group).await?;
@@ -55,13 +62,11 @@ async fn get_repo(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
     let repo_id = &path_params.repo_id;
 
     // Fetch the backend and the group
-    let crypto_key = create_veilid_cryptokey_from_base64(group_id)?;
     let backend = get_backend().await?;
-    let group = backend.get_group(&crypto_key).await?;
+    let group = find_group(&backend, group_id).await?;
 
     // Fetch the repo from the group
-    let repo_crypto_key = create_veilid_cryptokey_from_base64(repo_id)?;
-    let repo = group.get_repo(&repo_crypto_key).await?;
+    let repo = find_repo(&group, repo_id).await?;
 
     // Now, convert the owned Repo into SnowbirdRepo
     let snowbird_repo: SnowbirdRepo = repo.into();
@@ -69,6 +74,30 @@ async fn get_repo(path: web::Path<GroupRepoPath>) -> AppResult<impl Responder> {
     Ok(HttpResponse::Ok().json(snowbird_repo))
 }
 
+#[patch("")]
+async fn rename_repo(
+    path: web::Path<GroupRepoPath>,
+    body: web::Json<RequestName>,
+) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let path_params = path.into_inner();
+    let name = validate_name(&body.name)?;
+
+    let backend = get_backend().await?;
+    let group = find_group(&backend, &path_params.group_id).await?;
+    let repo = find_repo(&group, &path_params.repo_id).await?;
+    ensure_writable(&repo)?;
+
+    // Writes the name to the repo's DHT record, so other members see it on refresh.
+    repo.set_name(&name).await?;
+    log_debug!(TAG, "Renamed repo {}", repo.id());
+
+    let mut snowbird_repo: SnowbirdRepo = repo.into();
+    snowbird_repo.name = name;
+
+    Ok(HttpResponse::Ok().json(snowbird_repo))
+}
+
 #[post("")]
 async fn create_repo(
     path: web::Path<String>,
@@ -84,8 +113,7 @@ async fn create_repo(
 
     let backend = get_backend().await?;
 
-    let crypto_key = create_veilid_cryptokey_from_base64(&group_id)?;
-    let mut group = backend.get_group(&crypto_key).await?;
+    let mut group = find_group(&backend, &group_id).await?;
 
     // Joining a group now auto-creates a writable repo (save-dweb-backend fix).
     // The Android UI may still call this endpoint after joining; make it idempotent.
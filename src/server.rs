@@ -1,25 +1,35 @@
 #![allow(unused)]
+use crate::config::ServerConfig;
 use crate::constants::{self, TAG, VERSION};
-use crate::error::{AppError, AppResult};
+use crate::error::{ApiError, AppError, AppResult};
+use crate::events::{self, ServerEvent};
 use crate::groups;
+use crate::jobs;
 use crate::logging::android_log;
+use crate::pins;
 use crate::repos;
+use crate::storage;
 use crate::{log_debug, log_error, log_info};
+use actix_web::dev::ServerHandle;
 use actix_web::{get, post};
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use anyhow::{anyhow, Context, Result};
 use num_cpus;
 use once_cell::sync::OnceCell;
 use save_dweb_backend::backend::Backend;
 use serde::Deserialize;
 use serde_json::json;
-use std::net::Ipv4Addr;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use std::{env, panic};
+use std::{env, fs, panic};
 use thiserror::Error;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
 
+use crate::actix_bearer_auth::BearerAuth;
+use crate::actix_legacy_errors::LegacyErrors;
 use crate::actix_route_dumper::RouteDumper;
 use crate::models::SnowbirdGroup;
 #[cfg(test)]
@@ -43,12 +53,84 @@ pub static BACKEND: OnceCell<Arc<Backend>> = OnceCell::new();
 #[cfg(test)]
 pub static BACKEND: RwLock<Option<Arc<Backend>>> = RwLock::new(None);
 
+// Directory for server-side state that the backend does not track (media metadata etc.).
+// Unset in tests that skip `start`, in which case that state is kept in memory only.
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+pub fn data_dir() -> Option<&'static Path> {
+    DATA_DIR.get().map(PathBuf::as_path)
+}
+
+// Lifecycle state shared by `POST /api/shutdown`, desktop signal handling and JNI `stopServer`.
+static SERVER_HANDLE: Mutex<Option<ServerHandle>> = Mutex::new(None);
+static SOCKET_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+static SHUTDOWN_REQUESTED: Notify = Notify::const_new();
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+// The pin sync loop only ends with the process-wide event channel, so it is aborted here.
+static PIN_SYNC: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+// How long a shutdown waits for in-flight responses, such as large downloads, to finish.
+const SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+
+// Token the `/api` scope currently requires; handed to the host app through the bridges.
+static API_TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+// Config of the running server; request handlers read their limits from it.
+static CONFIG: Mutex<Option<ServerConfig>> = Mutex::new(None);
+
+/// The bearer token of the running server, if it has been started.
+pub fn api_token() -> Option<String> {
+    API_TOKEN.lock().ok()?.clone()
+}
+
+/// The config of the running server, or the defaults before it starts.
+pub fn config() -> ServerConfig {
+    CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.clone())
+        .unwrap_or_default()
+}
+
+pub(crate) fn set_config(config: Option<ServerConfig>) {
+    if let Ok(mut current) = CONFIG.lock() {
+        *current = config;
+    }
+}
+
+/// Fill in a random token when `config` has none, or only a blank one, and publish it via
+/// `api_token`.
+///
+/// Bridges call this before spawning the server thread so the host can read the token
+/// immediately; `start_with_config` calls it again, which keeps the same token.
+pub fn ensure_api_token(config: &mut ServerConfig) -> String {
+    // Every entry point passes through here, so a blank token from any of them is replaced.
+    let token = config
+        .api_token
+        .take()
+        .filter(|token| !token.trim().is_empty())
+        .unwrap_or_else(generate_api_token);
+    config.api_token = Some(token.clone());
+    if let Ok(mut api_token) = API_TOKEN.lock() {
+        *api_token = Some(token.clone());
+    }
+    token
+}
+
+fn generate_api_token() -> String {
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64_url::encode(&bytes)
+}
+
 pub async fn get_backend() -> Result<Arc<Backend>, anyhow::Error> {
     #[cfg(not(test))]
     {
         match BACKEND.get() {
             Some(backend) => Ok(Arc::clone(backend)),
-            None => Err(anyhow!("Backend not initialized")),
+            None => Err(ApiError::BackendNotReady("backend not initialized".to_string()).into()),
         }
     }
     #[cfg(test)]
@@ -58,7 +140,7 @@ pub async fn get_backend() -> Result<Arc<Backend>, anyhow::Error> {
             .map_err(|e| anyhow!("Failed to read backend lock: {e}"))?;
         match backend_lock.as_ref() {
             Some(backend) => Ok(Arc::clone(backend)),
-            None => Err(anyhow!("Backend not initialized")),
+            None => Err(ApiError::BackendNotReady("backend not initialized".to_string()).into()),
         }
     }
 }
@@ -88,9 +170,11 @@ pub async fn ensure_backend_ready() -> AppResult<()> {
     // This will fail gracefully if not initialized
     match backend.get_iroh_blobs().await {
         Some(_) => Ok(()),
-        None => Err(crate::error::AppError::from(anyhow!(
-            "Backend not ready. Veilid Iroh Blobs API not initialized. Initialization may still be in progress."
-        ))),
+        None => Err(ApiError::BackendNotReady(
+            "Veilid Iroh Blobs API not initialized. Initialization may still be in progress."
+                .to_string(),
+        )
+        .into()),
     }
 }
 
@@ -118,9 +202,7 @@ async fn health_ready() -> AppResult<impl Responder> {
     let backend = get_backend().await?;
 
     if !backend.is_initialized().await {
-        return Err(crate::error::AppError::from(anyhow!(
-            "Backend not ready. Initialization in progress."
-        )));
+        return Err(ApiError::BackendNotReady("initialization in progress".to_string()).into());
     }
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
@@ -129,6 +211,30 @@ async fn health_ready() -> AppResult<impl Responder> {
     })))
 }
 
+/// Requests over the Unix socket have no peer address; TCP peers must be loopback.
+fn is_local_request(req: &HttpRequest) -> bool {
+    match req.peer_addr() {
+        None => true,
+        Some(addr) => addr.ip().is_loopback(),
+    }
+}
+
+#[post("/shutdown")]
+async fn shutdown_server(req: HttpRequest) -> AppResult<impl Responder> {
+    if !is_local_request(&req) {
+        return Err(ApiError::Forbidden(
+            "shutdown is only allowed from the local device".to_string(),
+        )
+        .into());
+    }
+
+    log_info!(TAG, "Shutdown requested over HTTP");
+    // `start` performs the shutdown so this request can finish draining like any other.
+    request_shutdown();
+
+    Ok(HttpResponse::Accepted().json(json!({ "status": "shutting_down" })))
+}
+
 #[derive(Deserialize)]
 struct JoinGroupRequest {
     uri: String,
@@ -183,6 +289,25 @@ fn get_optimal_worker_count() -> usize {
 }
 
 pub async fn start(backend_base_directory: &str, server_socket_path: &str) -> anyhow::Result<()> {
+    start_with_config(
+        backend_base_directory,
+        server_socket_path,
+        ServerConfig::from_env(),
+    )
+    .await
+}
+
+pub async fn start_with_config(
+    backend_base_directory: &str,
+    server_socket_path: &str,
+    mut config: ServerConfig,
+) -> anyhow::Result<()> {
+    config.validate()?;
+    let api_token = ensure_api_token(&mut config);
+    set_config(Some(config.clone()));
+    // A previous start in this process may have been shut down already.
+    SHUTTING_DOWN.store(false, Ordering::SeqCst);
+    events::reopen_streams();
     log_debug!(
         TAG,
         "start_server: Using socket path: {:?}",
@@ -194,8 +319,14 @@ pub async fn start(backend_base_directory: &str, server_socket_path: &str) -> an
     let start_instant = Instant::now();
     log_info!(TAG, "Starting server initialization...");
 
-    let lan_address = Ipv4Addr::LOCALHOST; // 127.0.0.1
-    let lan_port = 8080;
+    log_info!(
+        TAG,
+        "Listening config: tcp={} ({}:{}), socket={}",
+        config.enable_tcp,
+        config.bind_address,
+        config.port,
+        config.enable_socket
+    );
 
     panic::set_hook(Box::new(|panic_info| {
         log_error!(TAG, "Panic occurred: {:?}", panic_info);
@@ -206,6 +337,7 @@ pub async fn start(backend_base_directory: &str, server_socket_path: &str) -> an
     }
 
     let backend_path = Path::new(backend_base_directory);
+    let _ = DATA_DIR.set(backend_path.join("save-rust"));
 
     #[cfg(not(test))]
     BACKEND.get_or_init(|| init_backend(backend_path));
@@ -234,7 +366,11 @@ pub async fn start(backend_base_directory: &str, server_socket_path: &str) -> an
         tokio::spawn(async move {
             if let Err(e) = backend_arc.start().await {
                 log_error!(TAG, "Backend failed to start: {:?}", e);
+                return;
             }
+            events::publish(ServerEvent::BackendReady { ready: true });
+            spawn_pin_sync();
+            events::forward_backend_updates(&backend_arc).await;
         });
     } else {
         log_error!(TAG, "Backend not initialized; cannot start in background");
@@ -242,33 +378,156 @@ pub async fn start(backend_base_directory: &str, server_socket_path: &str) -> an
 
     log_perf("Backend init scheduled", start_instant.elapsed());
 
-    let web_server = HttpServer::new(move || {
+    let mut web_server = HttpServer::new(move || {
         let app_start = Instant::now();
         let app = App::new()
+            .wrap(LegacyErrors)
             .wrap(RouteDumper::new(actix_log))
             .service(status)
             .service(health)
             .service(health_ready)
             .service(
                 web::scope("/api")
+                    .wrap(BearerAuth::new(api_token.as_str()))
                     .service(join_group)
-                    .service(groups::scope()),
+                    .service(groups::scope())
+                    .service(jobs::scope())
+                    .service(storage::scope())
+                    .service(events::event_stream)
+                    .service(shutdown_server),
             );
         log_perf("Web server app created", app_start.elapsed());
         app
     })
-    .bind_uds(server_socket_path)?
-    .bind((lan_address, lan_port))?
     .disable_signals()
+    .shutdown_timeout(SHUTDOWN_TIMEOUT_SECS)
     .workers(worker_count);
 
+    if config.enable_socket {
+        web_server = web_server.bind_uds(server_socket_path)?;
+    }
+    if config.enable_tcp {
+        web_server = web_server.bind((config.bind_address, config.port))?;
+    }
+
     log_perf("Web server initialized", start_instant.elapsed());
     log_info!(TAG, "Starting web server...");
 
     let server_future = web_server.run();
     log_perf("Web server started", start_instant.elapsed());
 
-    server_future.await.context("Failed to start server")
+    if let Ok(mut handle) = SERVER_HANDLE.lock() {
+        *handle = Some(server_future.handle());
+    }
+    if config.enable_socket {
+        if let Ok(mut socket_path) = SOCKET_PATH.lock() {
+            *socket_path = Some(PathBuf::from(server_socket_path));
+        }
+    }
+
+    tokio::pin!(server_future);
+    tokio::select! {
+        result = &mut server_future => {
+            remove_socket_file();
+            return result.context("Failed to start server");
+        }
+        _ = SHUTDOWN_REQUESTED.notified() => {}
+    }
+
+    // Keep driving the server while it drains so in-flight requests can complete.
+    let (shutdown_result, server_result) = tokio::join!(shutdown(), server_future);
+    shutdown_result?;
+    server_result.context("Failed to stop server")
+}
+
+/// Ask a running `start` to shut everything down. Safe to call from any thread or runtime.
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.notify_one();
+}
+
+fn spawn_pin_sync() {
+    let Ok(mut pin_sync) = PIN_SYNC.lock() else {
+        return;
+    };
+    if let Some(previous) = pin_sync.take() {
+        previous.abort();
+    }
+    // A shutdown that began while the backend was starting has already stopped the loop.
+    if !SHUTTING_DOWN.load(Ordering::SeqCst) {
+        *pin_sync = Some(tokio::spawn(pins::keep_pins_synced()));
+    }
+}
+
+fn stop_pin_sync() {
+    let pin_sync = PIN_SYNC
+        .lock()
+        .ok()
+        .and_then(|mut pin_sync| pin_sync.take());
+    if let Some(pin_sync) = pin_sync {
+        pin_sync.abort();
+    }
+}
+
+/// Whether a started server's pin sync loop is still running.
+pub(crate) fn pin_sync_running() -> bool {
+    PIN_SYNC
+        .lock()
+        .is_ok_and(|pin_sync| pin_sync.as_ref().is_some_and(|task| !task.is_finished()))
+}
+
+/// Stop accepting requests and drain in-flight ones, then stop the backend, shut down the
+/// Veilid API and remove the Unix socket file. Only the first call after each start does
+/// any work.
+pub async fn shutdown() -> anyhow::Result<()> {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        log_debug!(TAG, "Shutdown already in progress");
+        return Ok(());
+    }
+    log_info!(TAG, "Shutting down server...");
+
+    let handle = SERVER_HANDLE
+        .lock()
+        .ok()
+        .and_then(|mut handle| handle.take());
+    if let Some(handle) = handle {
+        // Event streams would otherwise keep the drain waiting until the timeout.
+        events::close_streams();
+        handle.stop(true).await;
+        log_info!(TAG, "Web server stopped");
+    }
+
+    stop_pin_sync();
+
+    stop().await?;
+
+    if let Ok(backend) = get_backend().await {
+        if let Some(veilid_api) = backend.get_veilid_api().await {
+            veilid_api.shutdown().await;
+            log_info!(TAG, "Veilid API shut down successfully");
+        }
+    }
+
+    remove_socket_file();
+    if let Ok(mut api_token) = API_TOKEN.lock() {
+        *api_token = None;
+    }
+    set_config(None);
+
+    // Give background tasks a moment to observe the shutdown.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    Ok(())
+}
+
+fn remove_socket_file() {
+    let socket_path = SOCKET_PATH.lock().ok().and_then(|mut path| path.take());
+    if let Some(socket_path) = socket_path {
+        match fs::remove_file(&socket_path) {
+            Ok(_) => log_debug!(TAG, "Removed socket file {:?}", socket_path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log_error!(TAG, "Failed to remove socket file {:?}: {}", socket_path, e),
+        }
+    }
 }
 
 pub async fn stop() -> anyhow::Result<()> {
@@ -278,6 +537,7 @@ pub async fn stop() -> anyhow::Result<()> {
         Ok(_) => log_debug!(TAG, "Backend shut down successfully."),
         Err(e) => log_error!(TAG, "Failed to shut down backend: {:?}", e),
     }
+    events::publish(ServerEvent::BackendReady { ready: false });
 
     Ok(())
 }
@@ -0,0 +1,524 @@
+use crate::constants::TAG;
+use crate::error::{ApiError, AppResult};
+use crate::history;
+use crate::metadata;
+use crate::pins;
+use crate::server::{ensure_backend_ready, get_backend};
+use crate::utils::{create_veilid_cryptokey_from_base64, unix_timestamp};
+use crate::{log_debug, log_info};
+use actix_web::{get, post, web, HttpResponse, Responder, Scope};
+use iroh_blobs::get::db::valid_ranges;
+use iroh_blobs::hashseq::HashSeq;
+use iroh_blobs::store::fs::Store as BlobStore;
+use iroh_blobs::store::{Map, MapEntry, MapMut, PossiblyPartialEntry, ReadableStore, Store};
+use iroh_blobs::{BlobFormat, Hash};
+use iroh_io::AsyncSliceReaderExt;
+use save_dweb_backend::backend::Backend;
+use save_dweb_backend::common::DHTEntity;
+use save_dweb_backend::repo::Repo;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+// Collection hashes are normally served from the local DHT record cache.
+const COLLECTION_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Shared by everything that adds blobs or is about to open one, exclusive while collecting
+// garbage or evicting, so a blob stored just before its collection is updated is never
+// mistaken for an orphan and a blob that was just checked is not deleted before it is read.
+// The exclusive side only ever uses `try_write`, so a task may hold several shared guards.
+static BLOB_WRITES: RwLock<()> = RwLock::const_new(());
+
+/// Held while adding blobs to the store or opening them; garbage collection and eviction
+/// refuse to run while one is held.
+pub async fn blob_write_guard() -> RwLockReadGuard<'static, ()> {
+    BLOB_WRITES.read().await
+}
+
+/// Disk usage of one repo's files. Files shared with other repos are counted in each.
+#[derive(Debug, Default, Serialize)]
+pub struct RepoUsage {
+    pub repo_id: String,
+    pub name: String,
+    pub can_write: bool,
+    pub collection_hash: Option<String>,
+    pub local_files: usize,
+    pub local_bytes: u64,
+    pub remote_only_files: usize,
+    /// Size of remote-only files, counting only files whose size is known.
+    pub remote_only_bytes: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GroupUsage {
+    pub group_id: String,
+    pub name: Option<String>,
+    pub local_bytes: u64,
+    pub remote_only_files: usize,
+    pub remote_only_bytes: u64,
+    pub repos: Vec<RepoUsage>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OrphanUsage {
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct StorageReport {
+    /// Every complete blob in the store, including collections and orphans.
+    pub total_bytes: u64,
+    pub groups: Vec<GroupUsage>,
+    pub orphaned: OrphanUsage,
+    /// False when a repo's collection could not be resolved, in which case `orphaned` may
+    /// include blobs that repo still uses.
+    pub complete: bool,
+}
+
+struct Scan {
+    report: StorageReport,
+    orphans: Vec<Hash>,
+}
+
+/// How `POST /api/storage/evict` picks downloaded files to drop. Files that were never
+/// viewed count as the least recently viewed.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Every file not viewed for at least `secs` seconds.
+    OlderThan { secs: u64 },
+    /// The `files` least recently viewed files.
+    LeastRecentlyViewed { files: usize },
+    /// Least recently viewed files until the evictable files use at most `max_bytes`.
+    ByteBudget { max_bytes: u64 },
+}
+
+#[derive(Debug, Deserialize)]
+struct EvictRequest {
+    #[serde(flatten)]
+    policy: EvictionPolicy,
+    /// Limit eviction to one group, or one repo when `repo_id` is also set.
+    group_id: Option<String>,
+    repo_id: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// A downloaded file of another member's repo that can be dropped locally.
+#[derive(Debug, Clone, Serialize)]
+pub struct EvictionCandidate {
+    pub group_id: String,
+    pub repo_id: String,
+    pub file_name: String,
+    pub size: u64,
+    pub last_viewed_at: Option<u64>,
+    #[serde(skip)]
+    hash: Hash,
+}
+
+pub fn scope() -> Scope {
+    web::scope("/storage")
+        .service(storage_report)
+        .service(collect_garbage)
+        .service(evict_files)
+}
+
+pub(crate) async fn blob_store(backend: &Backend) -> AppResult<BlobStore> {
+    let blobs = backend.get_iroh_blobs().await.ok_or_else(|| {
+        ApiError::BackendNotReady("Veilid Iroh Blobs API not initialized".to_string())
+    })?;
+    Ok(blobs.store().clone())
+}
+
+/// Size of a blob if it is stored completely.
+pub(crate) async fn local_blob_size(store: &BlobStore, hash: &Hash) -> Option<u64> {
+    let entry = store.get(hash).await.ok()??;
+    entry.is_complete().then(|| entry.size().value())
+}
+
+/// Bytes of a blob stored so far, counting the verified chunks of a download still in
+/// progress. `None` when nothing of it is stored.
+pub(crate) async fn stored_bytes(store: &BlobStore, hash: &Hash) -> Option<u64> {
+    match store.get_possibly_partial(hash).await.ok()? {
+        PossiblyPartialEntry::Complete(entry) => Some(entry.size().value()),
+        PossiblyPartialEntry::Partial(entry) => {
+            let size = entry.size().value();
+            let ranges = valid_ranges::<BlobStore>(&entry).await.ok()?;
+            // Boundaries alternate between range starts and ends; the last range may be open.
+            let bytes = ranges
+                .boundaries()
+                .chunks(2)
+                .map(|range| {
+                    let start = range[0].to_bytes().min(size);
+                    let end = range.get(1).map_or(size, |end| end.to_bytes().min(size));
+                    end - start
+                })
+                .sum();
+            Some(bytes)
+        }
+        PossiblyPartialEntry::NotFound => None,
+    }
+}
+
+/// Hashes a stored hash sequence links to, or `None` when it is not stored completely.
+pub(crate) async fn hash_seq_links(
+    store: &BlobStore,
+    hash: &Hash,
+) -> anyhow::Result<Option<Vec<Hash>>> {
+    let Some(entry) = store.get(hash).await? else {
+        return Ok(None);
+    };
+    if !entry.is_complete() {
+        return Ok(None);
+    }
+    let data = entry.data_reader().await?.read_to_end().await?;
+    Ok(Some(HashSeq::try_from(data)?.iter().collect()))
+}
+
+/// Walk every joined group and the blob store, marking each blob a collection or tag
+/// still references.
+async fn scan(backend: &Backend, store: &BlobStore) -> AppResult<Scan> {
+    let mut referenced = HashSet::new();
+    let mut complete = true;
+    let mut groups = Vec::new();
+
+    for group in backend.list_groups().await? {
+        let mut group_usage = GroupUsage {
+            group_id: group.id().to_string(),
+            name: group.get_name().await.ok(),
+            ..GroupUsage::default()
+        };
+
+        let repos: Vec<Repo> = group.repos.lock().await.values().cloned().collect();
+        for repo in repos {
+            let (repo_usage, resolved) = scan_repo(store, &repo, &mut referenced).await;
+            complete &= resolved;
+            group_usage.local_bytes += repo_usage.local_bytes;
+            group_usage.remote_only_files += repo_usage.remote_only_files;
+            group_usage.remote_only_bytes += repo_usage.remote_only_bytes;
+            group_usage.repos.push(repo_usage);
+        }
+        groups.push(group_usage);
+    }
+
+    // Tags are the store's own roots; never collect what they protect.
+    for tag in store.tags().await? {
+        let (_, root) = tag?;
+        referenced.insert(root.hash);
+        if root.format == BlobFormat::HashSeq {
+            if let Some(links) = hash_seq_links(store, &root.hash).await? {
+                referenced.extend(links);
+            }
+        }
+    }
+
+    let mut report = StorageReport {
+        groups,
+        complete,
+        ..StorageReport::default()
+    };
+    let mut orphans = Vec::new();
+    for hash in store.blobs().await? {
+        let hash = hash?;
+        let Some(size) = local_blob_size(store, &hash).await else {
+            continue;
+        };
+        report.total_bytes += size;
+        if !referenced.contains(&hash) {
+            report.orphaned.blobs += 1;
+            report.orphaned.bytes += size;
+            orphans.push(hash);
+        }
+    }
+
+    Ok(Scan { report, orphans })
+}
+
+/// Measure one repo and add its collection and file blobs to `referenced`, along with those
+/// of the versions kept from its history when it is ours. Returns whether the collection
+/// was resolved, i.e. whether every blob the repo uses is now referenced.
+async fn scan_repo(
+    store: &BlobStore,
+    repo: &Repo,
+    referenced: &mut HashSet<Hash>,
+) -> (RepoUsage, bool) {
+    let repo_key = repo.id().to_string();
+    let mut usage = RepoUsage {
+        repo_id: repo_key.clone(),
+        name: repo.get_name().await.unwrap_or_default(),
+        can_write: repo.can_write(),
+        ..RepoUsage::default()
+    };
+
+    // Other members keep the history of their own repos.
+    if repo.can_write() {
+        for collection_hash in history::retained_collections(&repo_key) {
+            referenced.insert(collection_hash);
+            match hash_seq_links(store, &collection_hash).await {
+                Ok(Some(links)) => referenced.extend(links),
+                Ok(None) => {}
+                Err(e) => log_debug!(TAG, "Error reading version {}: {}", collection_hash, e),
+            }
+        }
+    }
+
+    let collection_hash =
+        match tokio::time::timeout(COLLECTION_LOOKUP_TIMEOUT, repo.get_hash_from_dht()).await {
+            Ok(Ok(hash)) => hash,
+            // A repo we own that has no files has never published a collection.
+            Ok(Err(_))
+                if repo.can_write()
+                    && repo.list_files().await.is_ok_and(|files| files.is_empty()) =>
+            {
+                return (usage, true);
+            }
+            Ok(Err(e)) => {
+                usage.error = Some(format!("Error getting repo hash from DHT: {e}"));
+                return (usage, false);
+            }
+            Err(_) => {
+                usage.error = Some("Timed out getting repo hash from DHT".to_string());
+                return (usage, false);
+            }
+        };
+    usage.collection_hash = Some(collection_hash.to_string());
+    referenced.insert(collection_hash);
+
+    match hash_seq_links(store, &collection_hash).await {
+        Ok(Some(links)) => referenced.extend(links),
+        // Without the collection we cannot tell which local blobs the repo still uses.
+        Ok(None) => {
+            usage.error = Some("Collection not downloaded yet".to_string());
+            return (usage, false);
+        }
+        Err(e) => {
+            usage.error = Some(format!("Error reading collection: {e}"));
+            return (usage, false);
+        }
+    }
+
+    let files = match repo.list_files().await {
+        Ok(files) => files,
+        Err(e) => {
+            usage.error = Some(format!("Error listing files: {e}"));
+            return (usage, true);
+        }
+    };
+    for file_name in files {
+        let hash = match repo.get_file_hash(&file_name).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                log_debug!(TAG, "Error getting hash of {}: {}", file_name, e);
+                continue;
+            }
+        };
+        match local_blob_size(store, &hash).await {
+            Some(size) => {
+                usage.local_files += 1;
+                usage.local_bytes += size;
+            }
+            None => {
+                usage.remote_only_files += 1;
+                usage.remote_only_bytes += metadata::get_current(&repo_key, &file_name, &hash)
+                    .size
+                    .unwrap_or(0);
+            }
+        }
+    }
+
+    (usage, true)
+}
+
+#[get("")]
+async fn storage_report() -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let store = blob_store(&backend).await?;
+
+    let scan = scan(&backend, &store).await?;
+    Ok(HttpResponse::Ok().json(scan.report))
+}
+
+#[post("/gc")]
+async fn collect_garbage() -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let backend = get_backend().await?;
+    let store = blob_store(&backend).await?;
+
+    let Ok(_writes) = BLOB_WRITES.try_write() else {
+        return Err(
+            ApiError::GcUnavailable("uploads or downloads are in progress".to_string()).into(),
+        );
+    };
+    let scan = scan(&backend, &store).await?;
+    if !scan.report.complete {
+        return Err(ApiError::GcUnavailable(
+            "some repos could not be resolved, see GET /api/storage".to_string(),
+        )
+        .into());
+    }
+
+    let deleted_blobs = scan.orphans.len();
+    let freed_bytes = scan.report.orphaned.bytes;
+    if !scan.orphans.is_empty() {
+        store.delete(scan.orphans).await?;
+    }
+    log_info!(
+        TAG,
+        "Garbage collection deleted {} blobs, freeing {} bytes",
+        deleted_blobs,
+        freed_bytes
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "deleted_blobs": deleted_blobs,
+        "freed_bytes": freed_bytes,
+    })))
+}
+
+/// Downloaded files of other members' unpinned repos within the request's scope, least
+/// recently viewed first. Blobs also used by our own or pinned repos are left out, as is
+/// every collection, so listings keep working after eviction.
+async fn eviction_candidates(
+    backend: &Backend,
+    store: &BlobStore,
+    request: &EvictRequest,
+) -> AppResult<Vec<EvictionCandidate>> {
+    let mut candidates = Vec::new();
+    let mut protected = HashSet::new();
+
+    for group in backend.list_groups().await? {
+        let group_id = group.id().to_string();
+        let repos: Vec<Repo> = group.repos.lock().await.values().cloned().collect();
+        for repo in repos {
+            let repo_id = repo.id().to_string();
+            let in_scope = request.group_id.as_ref().is_none_or(|id| *id == group_id)
+                && request.repo_id.as_ref().is_none_or(|id| *id == repo_id);
+            let evictable =
+                in_scope && !repo.can_write() && !pins::is_repo_pinned(&group_id, &repo_id);
+
+            let files = match repo.list_files().await {
+                Ok(files) => files,
+                // Our own files must stay protected, so do not guess when they are unknown.
+                Err(e) if repo.can_write() => return Err(e.into()),
+                Err(e) => {
+                    log_debug!(TAG, "Skipping repo {} for eviction: {}", repo_id, e);
+                    continue;
+                }
+            };
+            for file_name in files {
+                let Ok(hash) = repo.get_file_hash(&file_name).await else {
+                    continue;
+                };
+                if !evictable {
+                    protected.insert(hash);
+                    continue;
+                }
+                let Some(size) = local_blob_size(store, &hash).await else {
+                    continue;
+                };
+                candidates.push(EvictionCandidate {
+                    group_id: group_id.clone(),
+                    repo_id: repo_id.clone(),
+                    last_viewed_at: metadata::get_current(&repo_id, &file_name, &hash)
+                        .last_viewed_at,
+                    file_name,
+                    size,
+                    hash,
+                });
+            }
+        }
+    }
+
+    candidates.retain(|candidate| !protected.contains(&candidate.hash));
+    candidates.sort_by_key(|candidate| candidate.last_viewed_at.unwrap_or(0));
+    // Identical content is stored once; keep one entry per blob so sizes are not doubled.
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.hash));
+
+    Ok(candidates)
+}
+
+/// Pick the files `policy` evicts from `candidates`, which are least recently viewed first.
+fn select_for_eviction(
+    policy: EvictionPolicy,
+    candidates: Vec<EvictionCandidate>,
+    now: u64,
+) -> Vec<EvictionCandidate> {
+    match policy {
+        EvictionPolicy::OlderThan { secs } => candidates
+            .into_iter()
+            .filter(|candidate| now.saturating_sub(candidate.last_viewed_at.unwrap_or(0)) >= secs)
+            .collect(),
+        EvictionPolicy::LeastRecentlyViewed { files } => {
+            candidates.into_iter().take(files).collect()
+        }
+        EvictionPolicy::ByteBudget { max_bytes } => {
+            let mut used: u64 = candidates.iter().map(|candidate| candidate.size).sum();
+            candidates
+                .into_iter()
+                .take_while(|candidate| {
+                    let over_budget = used > max_bytes;
+                    used = used.saturating_sub(candidate.size);
+                    over_budget
+                })
+                .collect()
+        }
+    }
+}
+
+#[post("/evict")]
+async fn evict_files(request: web::Json<EvictRequest>) -> AppResult<impl Responder> {
+    ensure_backend_ready().await?;
+    let mut request = request.into_inner();
+    if request.repo_id.is_some() && request.group_id.is_none() {
+        return Err(ApiError::BadRequest("repo_id requires group_id".to_string()).into());
+    }
+    // Compare against canonical IDs even when the client sent the legacy format.
+    let canonical = |id: Option<String>| -> AppResult<Option<String>> {
+        id.map(|id| create_veilid_cryptokey_from_base64(&id).map(|key| key.to_string()))
+            .transpose()
+    };
+    request.group_id = canonical(request.group_id)?;
+    request.repo_id = canonical(request.repo_id)?;
+    let backend = get_backend().await?;
+    let store = blob_store(&backend).await?;
+
+    // A dry run deletes nothing, so it need not wait for transfers to finish.
+    let _writes = if request.dry_run {
+        None
+    } else {
+        let Ok(writes) = BLOB_WRITES.try_write() else {
+            return Err(ApiError::EvictionUnavailable(
+                "uploads or downloads are in progress".to_string(),
+            )
+            .into());
+        };
+        Some(writes)
+    };
+    let candidates = eviction_candidates(&backend, &store, &request).await?;
+    let evicted = select_for_eviction(request.policy, candidates, unix_timestamp());
+    let freed_bytes: u64 = evicted.iter().map(|candidate| candidate.size).sum();
+
+    if !request.dry_run && !evicted.is_empty() {
+        store
+            .delete(evicted.iter().map(|candidate| candidate.hash).collect())
+            .await?;
+        log_info!(
+            TAG,
+            "Evicted {} downloaded files, freeing {} bytes",
+            evicted.len(),
+            freed_bytes
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "dry_run": request.dry_run,
+        "evicted": evicted,
+        "freed_bytes": freed_bytes,
+    })))
+}
@@ -0,0 +1,126 @@
+use crate::utils::{load_json_state, save_json_state, unix_timestamp};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+const SYNC_STATE_FILE: &str = "sync-state.json";
+
+/// What a repo looked like the last time `refresh` saw it change.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoSnapshot {
+    pub collection_hash: Option<String>,
+    /// File name to content hash.
+    pub files: BTreeMap<String, String>,
+    pub synced_at: u64,
+}
+
+/// A file whose content hash differs from the previous sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedFile {
+    pub name: String,
+    pub previous_hash: String,
+    pub current_hash: String,
+}
+
+/// Changes to a repo since its previous sync, as returned by `refresh`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncDiff {
+    pub previous_hash: Option<String>,
+    pub current_hash: Option<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedFile>,
+}
+
+// Keyed by repo ID.
+static SYNC_STATE: Lazy<Mutex<HashMap<String, RepoSnapshot>>> =
+    Lazy::new(|| Mutex::new(load_json_state(SYNC_STATE_FILE)));
+
+/// The snapshot recorded by the last sync of `repo_id`, if any.
+pub fn last_synced(repo_id: &str) -> Option<RepoSnapshot> {
+    SYNC_STATE
+        .lock()
+        .ok()
+        .and_then(|state| state.get(repo_id).cloned())
+}
+
+/// Compare `files` against the last sync of `repo_id` and remember them for the next one.
+///
+/// On the first sync every file is reported as added.
+pub fn record_sync(
+    repo_id: &str,
+    collection_hash: Option<String>,
+    files: BTreeMap<String, String>,
+) -> SyncDiff {
+    let current = RepoSnapshot {
+        collection_hash,
+        files,
+        synced_at: unix_timestamp(),
+    };
+
+    let Ok(mut state) = SYNC_STATE.lock() else {
+        return diff(None, &current);
+    };
+    let sync_diff = diff(state.get(repo_id), &current);
+    if state
+        .get(repo_id)
+        .map(|previous| (&previous.collection_hash, &previous.files))
+        != Some((&current.collection_hash, &current.files))
+    {
+        state.insert(repo_id.to_string(), current);
+        save_json_state(SYNC_STATE_FILE, &*state);
+    }
+    sync_diff
+}
+
+/// Report an unchanged collection without re-reading its files.
+pub fn unchanged(previous: &RepoSnapshot) -> SyncDiff {
+    SyncDiff {
+        previous_hash: previous.collection_hash.clone(),
+        current_hash: previous.collection_hash.clone(),
+        ..SyncDiff::default()
+    }
+}
+
+/// Forget the sync state of repos that are no longer tracked.
+pub fn remove(repo_ids: &[String]) {
+    let Ok(mut state) = SYNC_STATE.lock() else {
+        return;
+    };
+    let before = state.len();
+    state.retain(|repo_id, _| !repo_ids.contains(repo_id));
+    if state.len() != before {
+        save_json_state(SYNC_STATE_FILE, &*state);
+    }
+}
+
+fn diff(previous: Option<&RepoSnapshot>, current: &RepoSnapshot) -> SyncDiff {
+    let empty = BTreeMap::new();
+    let previous_files = previous.map_or(&empty, |snapshot| &snapshot.files);
+
+    let mut sync_diff = SyncDiff {
+        previous_hash: previous.and_then(|snapshot| snapshot.collection_hash.clone()),
+        current_hash: current.collection_hash.clone(),
+        ..SyncDiff::default()
+    };
+
+    for (name, hash) in &current.files {
+        match previous_files.get(name) {
+            None => sync_diff.added.push(name.clone()),
+            Some(previous_hash) if previous_hash != hash => sync_diff.changed.push(ChangedFile {
+                name: name.clone(),
+                previous_hash: previous_hash.clone(),
+                current_hash: hash.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    sync_diff.removed = previous_files
+        .keys()
+        .filter(|name| !current.files.contains_key(*name))
+        .cloned()
+        .collect();
+
+    sync_diff
+}
@@ -1,8 +1,29 @@
-use crate::error::AppResult;
+use crate::constants::TAG;
+use crate::error::{ApiError, AppError, AppResult};
+use crate::events::{self, ServerEvent};
+use crate::log_error;
+use crate::server::{data_dir, get_backend};
+use crate::storage;
 use base64_url;
+use bytes::Bytes;
+use futures::Stream;
+use iroh_blobs::Hash;
+use save_dweb_backend::backend::Backend;
+use save_dweb_backend::common::DHTEntity;
+use save_dweb_backend::group::Group;
+use save_dweb_backend::repo::Repo;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::error::Elapsed;
 use veilid_core::{BareOpaqueRecordKey, BareRecordKey, RecordKey, CRYPTO_KIND_VLD0};
 
+// How often a fetch's stored bytes are measured for `fetch_progress` events.
+const FETCH_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Parse a RecordKey from either:
 /// - Typed format: "VLD0:base64_key:base64_hash" (from RecordKey::to_string())
 /// - Raw base64 format: "base64_key" (legacy)
@@ -12,15 +33,16 @@ pub fn create_veilid_cryptokey_from_base64(key_string: &str) -> AppResult<Record
         // BareRecordKey::try_decode expects "key:hash" (2 parts).
         // RecordKey::to_string() produces "KIND:key:hash" (3 parts), so drop the first segment.
         let bare_key = BareRecordKey::try_decode(bare_input)
-            .map_err(|e| anyhow::anyhow!("Invalid record key encoding: {e}"))?;
+            .map_err(|e| ApiError::InvalidKey(format!("invalid record key encoding: {e}")))?;
         return Ok(RecordKey::new(CRYPTO_KIND_VLD0, bare_key));
     }
 
     // Legacy: raw base64 format
-    let key_vec = base64_url::decode(key_string)?;
+    let key_vec = base64_url::decode(key_string)
+        .map_err(|e| ApiError::InvalidKey(format!("base64 decoding error: {e}")))?;
     let key_array: [u8; 32] = key_vec
         .try_into()
-        .map_err(|_| anyhow::anyhow!("Invalid key length: expected 32 bytes"))?;
+        .map_err(|_| ApiError::InvalidKey("expected 32 bytes".to_string()))?;
     let record_key = RecordKey::new(
         CRYPTO_KIND_VLD0,
         BareRecordKey::new(BareOpaqueRecordKey::from(&key_array[..]), None),
@@ -28,3 +50,205 @@ pub fn create_veilid_cryptokey_from_base64(key_string: &str) -> AppResult<Record
 
     Ok(record_key)
 }
+
+/// Look up a joined group by id, reporting a missing group as `group_not_found`.
+pub async fn find_group(backend: &Backend, group_id: &str) -> AppResult<Box<Group>> {
+    let key = create_veilid_cryptokey_from_base64(group_id)?;
+    backend
+        .get_group(&key)
+        .await
+        .map_err(|e| ApiError::GroupNotFound(format!("{group_id}: {e}")).into())
+}
+
+/// Look up a repo within `group`, reporting a missing repo as `repo_not_found`.
+pub async fn find_repo(group: &Group, repo_id: &str) -> AppResult<Box<Repo>> {
+    let key = create_veilid_cryptokey_from_base64(repo_id)?;
+    group
+        .get_repo(&key)
+        .await
+        .map_err(|e| ApiError::RepoNotFound(format!("{repo_id}: {e}")).into())
+}
+
+/// Trimmed display name for a group or repo, rejecting blank names as `bad_request`.
+pub fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".to_string()).into());
+    }
+    Ok(name.to_string())
+}
+
+/// Reject file names that are blank or contain a path separator as `bad_request`.
+pub fn validate_file_name(name: &str) -> AppResult<()> {
+    if name.trim().is_empty() || name.contains('/') {
+        return Err(ApiError::BadRequest(format!("invalid file name: {name:?}")).into());
+    }
+    Ok(())
+}
+
+/// Reject changes to a repo this node does not own, e.g. another member's repo.
+pub fn ensure_writable(repo: &Repo) -> AppResult<()> {
+    if repo.can_write() {
+        Ok(())
+    } else {
+        Err(ApiError::NotWritable(format!("{} belongs to another member", repo.id())).into())
+    }
+}
+
+/// Hash of a file in the repo's collection, reporting an unknown name as `file_not_found`.
+pub async fn find_file_hash(repo: &Repo, file_name: &str) -> AppResult<Hash> {
+    repo.get_file_hash(file_name)
+        .await
+        .map_err(|e| ApiError::FileNotFound(format!("{file_name}: {e}")).into())
+}
+
+/// Open a file for streaming its decrypted content. Callers look the file up with
+/// `find_file_hash` first, so a missing file is already reported as `file_not_found`.
+pub async fn open_decrypted_file(
+    repo: &Repo,
+    file_name: &str,
+) -> AppResult<(u64, impl Stream<Item = io::Result<Bytes>>)> {
+    repo.get_decrypted_file_stream(file_name)
+        .await
+        .map_err(|e| open_error(file_name, e))
+}
+
+/// Report a failure to decrypt as `decrypt_failure` and anything else, such as a store
+/// error, as an internal error. The backend has no error type for this, so its message is
+/// matched; `test_open_decrypted_file_errors` pins the messages this relies on.
+fn open_error(file_name: &str, e: anyhow::Error) -> AppError {
+    let decrypt_failed = e
+        .chain()
+        .any(|cause| cause.to_string().to_lowercase().contains("decrypt"));
+    if decrypt_failed {
+        ApiError::DecryptFailure(format!("{file_name}: {e}")).into()
+    } else {
+        e.context(format!("Failed to open {file_name}")).into()
+    }
+}
+
+/// Download a blob from peers, reporting an expired deadline as `peer_timeout`.
+pub async fn download_from_peers(group: &Group, hash: &Hash, timeout: Duration) -> AppResult<()> {
+    let _writing = storage::blob_write_guard().await;
+    match group
+        .download_hash_from_peers_with_timeout(hash, Some(timeout))
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) if e.chain().any(|cause| cause.is::<Elapsed>()) => {
+            Err(ApiError::PeerTimeout(format!("{hash} not received within {timeout:?}")).into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `download_from_peers`, calling `progress` with the bytes stored so far whenever that
+/// changes, checked every `interval`. The backend does not report progress itself.
+pub async fn download_with_progress<F>(
+    group: &Group,
+    hash: &Hash,
+    timeout: Duration,
+    interval: Duration,
+    mut progress: F,
+) -> AppResult<()>
+where
+    F: FnMut(u64),
+{
+    let backend = get_backend().await?;
+    let store = storage::blob_store(&backend).await?;
+
+    let download = download_from_peers(group, hash, timeout);
+    tokio::pin!(download);
+    let mut ticks = tokio::time::interval(interval);
+    let mut reported = None;
+    loop {
+        tokio::select! {
+            result = &mut download => return result,
+            _ = ticks.tick() => {
+                let stored = storage::stored_bytes(&store, hash).await.unwrap_or(0);
+                if reported != Some(stored) {
+                    reported = Some(stored);
+                    progress(stored);
+                }
+            }
+        }
+    }
+}
+
+/// `download_with_progress` for one file of a repo, publishing `fetch_progress` events so
+/// clients can follow downloads that are not jobs.
+pub async fn fetch_file_from_peers(
+    group: &Group,
+    repo_id: &str,
+    file_name: &str,
+    hash: &Hash,
+    timeout: Duration,
+) -> AppResult<()> {
+    let publish = |bytes_received: u64, done: bool| {
+        events::publish(ServerEvent::FetchProgress {
+            group_id: group.id().to_string(),
+            repo_id: repo_id.to_string(),
+            file_name: file_name.to_string(),
+            bytes_received,
+            done,
+        })
+    };
+    download_with_progress(group, hash, timeout, FETCH_PROGRESS_INTERVAL, |bytes| {
+        publish(bytes, false)
+    })
+    .await?;
+
+    let backend = get_backend().await?;
+    let store = storage::blob_store(&backend).await?;
+    let size = storage::local_blob_size(&store, hash).await.unwrap_or(0);
+    publish(size, true);
+    Ok(())
+}
+
+/// Seconds since the Unix epoch, used for timestamps in persisted server state.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Load a JSON state file from the server data directory, or the default when the
+/// file is missing, unreadable, or the data directory is not configured.
+pub fn load_json_state<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let Some(dir) = data_dir() else {
+        return T::default();
+    };
+    let path = dir.join(file_name);
+
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+            log_error!(TAG, "Ignoring corrupt state file {:?}: {}", path, e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Persist a JSON state file into the server data directory.
+///
+/// Writes to a temporary file first so a crash never leaves a truncated state file behind.
+/// Does nothing when the data directory is not configured.
+pub fn save_json_state<T: Serialize>(file_name: &str, state: &T) {
+    let Some(dir) = data_dir() else {
+        return;
+    };
+
+    let result = (|| -> anyhow::Result<()> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(file_name);
+        let tmp_path = dir.join(format!("{file_name}.tmp"));
+        fs::write(&tmp_path, serde_json::to_vec(state)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        log_error!(TAG, "Failed to persist state file {}: {}", file_name, e);
+    }
+}